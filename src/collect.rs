@@ -0,0 +1,137 @@
+//! Support for `Depythonizer::collect_errors`, which carries on past invalid values
+
+use std::cell::RefCell;
+use std::fmt;
+use std::iter;
+
+use serde::de::{self, value::MapDeserializer, value::SeqDeserializer};
+
+use crate::error::PythonizeError;
+
+/// A visitor which is given back if deserialization fails before it is used, so that a
+/// placeholder can be visited instead
+pub(crate) struct Recoverable<'v, V> {
+    slot: &'v RefCell<Option<V>>,
+}
+
+impl<'v, V> Recoverable<'v, V> {
+    pub(crate) fn new(slot: &'v RefCell<Option<V>>) -> Self {
+        Self { slot }
+    }
+
+    fn take(&self) -> V {
+        self.slot
+            .borrow_mut()
+            .take()
+            .expect("a visitor can only be used once")
+    }
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<E>(self, v: $ty) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                self.take().$method(v)
+            }
+        )*
+    };
+}
+
+impl<'de, V> de::Visitor<'de> for Recoverable<'_, V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match &*self.slot.borrow() {
+            Some(visitor) => visitor.expecting(formatter),
+            None => formatter.write_str("any value"),
+        }
+    }
+
+    forward_visit!(
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_borrowed_str(&'de str),
+        visit_string(String),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>),
+    );
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.take().visit_none()
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.take().visit_some(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        self.take().visit_unit()
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.take().visit_newtype_struct(deserializer)
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        self.take().visit_seq(seq)
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        self.take().visit_map(map)
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::EnumAccess<'de>,
+    {
+        self.take().visit_enum(data)
+    }
+}
+
+/// A sequence with no items, visited in place of an invalid sequence
+pub(crate) fn empty_seq() -> SeqDeserializer<iter::Empty<()>, PythonizeError> {
+    SeqDeserializer::new(iter::empty())
+}
+
+/// A mapping with no items, visited in place of an invalid mapping
+pub(crate) fn empty_map() -> MapDeserializer<'static, iter::Empty<((), ())>, PythonizeError> {
+    MapDeserializer::new(iter::empty())
+}
@@ -1,8 +1,28 @@
-use pyo3::{types::*, Bound};
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+#[cfg(any(not(Py_LIMITED_API), Py_3_11))]
+use std::ffi::CString;
+
+#[cfg(any(not(Py_LIMITED_API), Py_3_11))]
+use pyo3::buffer::{Element, ElementType, PyBuffer};
+use pyo3::exceptions::{PyAttributeError, PyBaseException, PyException, PyTypeError};
+use pyo3::sync::GILOnceCell;
+use pyo3::{ffi, intern, types::*, Bound, Py, PyResult, PyTypeInfo};
 use serde::de::{self, IntoDeserializer};
 use serde::Deserialize;
 
-use crate::error::{ErrorImpl, PythonizeError, Result};
+use crate::collect::{self, Recoverable};
+use crate::error::{ErrorImpl, Limit, PythonizeError, Result};
+use crate::exceptions::ExceptionClass;
+use crate::path::{Path, PathSegment};
+use crate::pybacked::{self, PYBACKED_TOKEN};
+use crate::rename::{self, RenameRule};
+use crate::shared::{self, DeSlot, SHARED_TOKEN};
 
 /// Attempt to convert a Python object to an instance of `T`
 pub fn depythonize<'a, 'py, T>(obj: &'a Bound<'py, PyAny>) -> Result<T>
@@ -12,98 +32,1488 @@ where
     T::deserialize(&mut Depythonizer::from_object(obj))
 }
 
+/// Attempt to convert a Python object to an instance of `T`, also returning the paths of the
+/// keys which `T` ignored, see [`UnknownKeys`]
+pub fn depythonize_with_ignored<'a, 'py, T>(obj: &'a Bound<'py, PyAny>) -> Result<(T, Vec<Path>)>
+where
+    T: Deserialize<'a>,
+{
+    let mut de = Depythonizer::from_object(obj).unknown_keys(UnknownKeys::Record);
+    let value = T::deserialize(&mut de)?;
+    Ok((value, de.ignored_keys()))
+}
+
+/// Check that a Python object can be converted to an instance of `T`, returning every error
+/// found with its path, see [`Depythonizer::collect_errors`].
+///
+/// `T`'s `Deserialize` implementation still runs, so that every check it makes applies, but
+/// the contents of strings and bytes which it takes ownership of, such as `String` and
+/// `serde_bytes::ByteBuf`, are only checked and not copied. Other values are built and dropped.
+/// Converted to a Python exception, the error lists each problem in an `errors` attribute.
+///
+/// A generic function can't be a `#[pyfunction]`, so expose it for a concrete type:
+///
+/// ```
+/// use pyo3::prelude::*;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Server {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// #[pyfunction]
+/// fn validate_servers(obj: &Bound<'_, PyAny>) -> PyResult<()> {
+///     Ok(pythonize::validate::<Vec<Server>>(obj)?)
+/// }
+/// ```
+pub fn validate<'a, 'py, T>(obj: &'a Bound<'py, PyAny>) -> Result<()>
+where
+    T: Deserialize<'a>,
+{
+    let mut de = Depythonizer::from_object(obj).collect_errors(true);
+    Rc::make_mut(&mut de.state).validating = true;
+    T::deserialize(&mut de)?;
+    Ok(())
+}
+
+/// The default maximum nesting depth of containers for `Depythonizer`
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// The default number of objects deserialized between checks for pending signals
+const DEFAULT_SIGNAL_CHECK_INTERVAL: usize = 1024;
+
+/// Limits on the size of the input accepted by a `Depythonizer`.
+///
+/// Each limit is checked before the corresponding Rust value is allocated, so that
+/// untrusted input cannot exhaust memory. All limits are disabled by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of objects nested inside the input, in total
+    pub max_nodes: Option<usize>,
+    /// Maximum number of items in a single sequence, set or mapping
+    pub max_length: Option<usize>,
+    /// Maximum length of a single str (in code points) or bytes object (in bytes)
+    pub max_str_length: Option<usize>,
+    /// Maximum bit length of a single int
+    pub max_int_bits: Option<usize>,
+}
+
+/// Conversions between loosely typed values which a `Depythonizer` may apply, for input such
+/// as configuration from environment variables, query strings or CSV files.
+///
+/// Each coercion only applies when the input does not already have the requested type. All
+/// coercions are disabled by default, see also [`Coercions::all`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Coercions {
+    /// Read integers and floats from str, as parsed by `int()` and `float()`
+    pub str_to_number: bool,
+    /// Read bools from the strs `"true"` and `"false"`, in any case
+    pub str_to_bool: bool,
+    /// Read strings from ints, in decimal
+    pub int_to_str: bool,
+    /// Read integers from floats without a fractional part
+    pub integral_float_to_int: bool,
+    /// Read strings from bytes containing UTF-8
+    pub bytes_to_str: bool,
+    /// Read a str, int, float or bool as a sequence of that one item
+    pub scalar_to_seq: bool,
+}
+
+impl Coercions {
+    /// Enable all coercions
+    pub fn all() -> Self {
+        Self {
+            str_to_number: true,
+            str_to_bool: true,
+            int_to_str: true,
+            integral_float_to_int: true,
+            bytes_to_str: true,
+            scalar_to_seq: true,
+        }
+    }
+}
+
+/// How a `Depythonizer` reads the variant of an enum from an object which is neither a str
+/// nor a mapping, see [`Depythonizer::class_tag`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClassTag {
+    /// The name of the object's class, or of the nearest base class named after a variant
+    ClassName,
+    /// The value of the given attribute of the object, which must be a str
+    Attribute(String),
+}
+
+/// How strictly a `Depythonizer` checks the types of Python objects, see
+/// [`Depythonizer::strictness`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Accept any object which converts to the requested type, such as any object as a bool
+    /// by its truthiness
+    #[default]
+    Lax,
+    /// Require `bool` for bools, `int` other than `bool` for integers, `float` for floats and
+    /// `str` for strings
+    Strict,
+    /// As `Strict`, and also reject instances of subclasses of `int`, `float`, `str` and the
+    /// builtin containers
+    Exact,
+}
+
+/// What a `Depythonizer` does with keys which the target type ignores, such as misspelled
+/// struct fields, see [`Depythonizer::unknown_keys`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownKeys {
+    /// Ignore them silently, like serde does unless a type uses `deny_unknown_fields`
+    #[default]
+    Ignore,
+    /// Record their paths, see [`Depythonizer::ignored_keys`]
+    Record,
+    /// Record their paths and emit a `PythonizeWarning`, a Python `UserWarning`, for each
+    Warn,
+    /// Fail with an error naming the path of the first one
+    Error,
+}
+
 /// A structure that deserializes Python objects into Rust values
 pub struct Depythonizer<'a, 'py> {
-    input: &'a Bound<'py, PyAny>,
+    /// Borrowed for `'a` where possible, so that str and bytes can be visited without copying
+    input: Cow<'a, Bound<'py, PyAny>>,
+    state: Rc<DepythonizerState<'py>>,
+    depth: usize,
+    /// `id()` of the container this deserializer is visiting, if any
+    visiting: Option<usize>,
+    /// The fields of the struct whose keys this deserializer's input belongs to, if any
+    fields: Option<&'static [&'static str]>,
+    /// Whether the input was returned by a fallback, which is then not applied to it again
+    substitute: bool,
+}
+
+/// A function returning a supported substitute for an object, see [`Depythonizer::fallback`]
+type Fallback<'py> = dyn Fn(&Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> + 'py;
+
+/// Values already produced for `Shared` objects, keyed by `id()`
+type SharedObjects<'py> = HashMap<usize, (Bound<'py, PyAny>, Rc<dyn Any>)>;
+
+/// A step into a Python object, converted to a [`PathSegment`] when needed
+#[derive(Clone)]
+enum PathEntry<'py> {
+    Key(Bound<'py, PyAny>),
+    Index(usize),
+}
+
+impl PathEntry<'_> {
+    fn to_segment(&self) -> PathSegment {
+        match self {
+            PathEntry::Key(key) => PathSegment::from_key(key),
+            PathEntry::Index(index) => PathSegment::Index(*index),
+        }
+    }
+}
+
+/// An iterator with its estimated length, if known
+type IteratorWithHint<'py> = (Bound<'py, PyIterator>, Option<usize>);
+
+/// State shared between a `Depythonizer` and the deserializers it creates for nested objects
+#[derive(Clone)]
+struct DepythonizerState<'py> {
+    max_depth: usize,
+    signal_check_interval: usize,
+    limits: Limits,
+    class_tag: Option<ClassTag>,
+    strictness: Strictness,
+    coercions: Coercions,
+    rename_fields: Option<RenameRule>,
+    rename_variants: Option<RenameRule>,
+    case_insensitive: bool,
+    unknown_keys: UnknownKeys,
+    collect_errors: bool,
+    warn_lossy: bool,
+    /// Whether the input is only validated, see [`validate`]
+    validating: bool,
+    fallback: Option<Rc<Fallback<'py>>>,
+    /// Fallbacks for instances of each class, checked in order before `fallback`
+    class_fallbacks: Vec<(Bound<'py, PyType>, Rc<Fallback<'py>>)>,
+    /// Steps to the object being deserialized, tracked if unknown keys or errors need a path
+    path: RefCell<Vec<PathEntry<'py>>>,
+    /// Paths of the unknown keys found so far
+    ignored_keys: RefCell<Vec<Path>>,
+    /// Errors collected so far, each with its path. Errors are not `Clone`, so this is shared
+    /// rather than copied when the state is, which only happens before deserializing.
+    errors: Rc<RefCell<Vec<PythonizeError>>>,
+    /// Number of deserializer calls collecting errors, the outermost reports them
+    collect_calls: Cell<usize>,
+    /// Whether an error which could not be replaced by a placeholder is being returned
+    unwinding: Cell<bool>,
+    /// Number of nested objects deserialized so far
+    nodes: Cell<usize>,
+    /// `id()` of the containers currently being visited, used to detect cycles
+    visiting: RefCell<HashSet<usize>>,
+    /// The Python objects are kept alive so that their `id()` cannot be reused
+    shared: RefCell<SharedObjects<'py>>,
+}
+
+impl Default for DepythonizerState<'_> {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            signal_check_interval: DEFAULT_SIGNAL_CHECK_INTERVAL,
+            limits: Limits::default(),
+            class_tag: None,
+            strictness: Strictness::Lax,
+            coercions: Coercions::default(),
+            rename_fields: None,
+            rename_variants: None,
+            case_insensitive: false,
+            unknown_keys: UnknownKeys::Ignore,
+            collect_errors: false,
+            warn_lossy: false,
+            validating: false,
+            fallback: None,
+            class_fallbacks: Vec::new(),
+            path: RefCell::default(),
+            ignored_keys: RefCell::default(),
+            errors: Rc::default(),
+            collect_calls: Cell::new(0),
+            unwinding: Cell::new(false),
+            nodes: Cell::new(0),
+            visiting: RefCell::default(),
+            shared: RefCell::default(),
+        }
+    }
 }
 
 impl<'a, 'py> Depythonizer<'a, 'py> {
     /// Create a deserializer from a Python object
     pub fn from_object(input: &'a Bound<'py, PyAny>) -> Self {
-        Depythonizer { input }
+        Depythonizer {
+            input: Cow::Borrowed(input),
+            state: Rc::default(),
+            depth: 0,
+            visiting: None,
+            fields: None,
+            substitute: false,
+        }
+    }
+
+    /// Set the maximum nesting depth of containers, 128 by default.
+    ///
+    /// Deeper input fails with an error instead of overflowing the stack.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        Rc::make_mut(&mut self.state).max_depth = max_depth;
+        self
+    }
+
+    /// Set how many objects are deserialized between checks for pending signals, 1024 by default.
+    ///
+    /// A signal handler which raises, such as the default `SIGINT` handler raising
+    /// `KeyboardInterrupt`, aborts deserialization with that exception. Use 0 to never check.
+    pub fn signal_check_interval(mut self, interval: usize) -> Self {
+        Rc::make_mut(&mut self.state).signal_check_interval = interval;
+        self
+    }
+
+    /// Set limits on the size of the input, see [`Limits`]
+    pub fn limits(mut self, limits: Limits) -> Self {
+        Rc::make_mut(&mut self.state).limits = limits;
+        self
+    }
+
+    /// Deserialize enums from instances of a class per variant, as well as from str and mappings.
+    ///
+    /// The variant is read from the object as described by `tag`, and the variant's fields are
+    /// read from the object's attributes. This matches Python APIs which model an enum as a base
+    /// class with a subclass for each variant, see also [`Pythonizer::variant_classes`].
+    ///
+    /// [`Pythonizer::variant_classes`]: crate::Pythonizer::variant_classes
+    pub fn class_tag(mut self, tag: ClassTag) -> Self {
+        Rc::make_mut(&mut self.state).class_tag = Some(tag);
+        self
+    }
+
+    /// Set how strictly the types of Python objects are checked, see [`Strictness`].
+    ///
+    /// Objects of the wrong type fail with an error naming the expected and actual types.
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        Rc::make_mut(&mut self.state).strictness = strictness;
+        self
+    }
+
+    /// Enable conversions between loosely typed values, see [`Coercions`]
+    pub fn coercions(mut self, coercions: Coercions) -> Self {
+        Rc::make_mut(&mut self.state).coercions = coercions;
+        self
+    }
+
+    /// Read struct fields named according to `rule`, rather than by their names in Rust.
+    ///
+    /// Keys which don't match a renamed field are read unchanged. This applies in addition to
+    /// any `#[serde(rename)]` attributes, see also
+    /// [`Pythonizer::rename_fields`](crate::Pythonizer::rename_fields).
+    pub fn rename_fields(mut self, rule: RenameRule) -> Self {
+        Rc::make_mut(&mut self.state).rename_fields = Some(rule);
+        self
+    }
+
+    /// Read enum variants named according to `rule`, rather than by their names in Rust.
+    ///
+    /// Names which don't match a renamed variant are read unchanged, see also
+    /// [`Pythonizer::rename_variants`](crate::Pythonizer::rename_variants).
+    pub fn rename_variants(mut self, rule: RenameRule) -> Self {
+        Rc::make_mut(&mut self.state).rename_variants = Some(rule);
+        self
+    }
+
+    /// Match struct fields and enum variants ignoring case, if there is no exact match
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        Rc::make_mut(&mut self.state).case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Set what to do with keys which the target type ignores, see [`UnknownKeys`]
+    pub fn unknown_keys(mut self, unknown_keys: UnknownKeys) -> Self {
+        Rc::make_mut(&mut self.state).unknown_keys = unknown_keys;
+        self
+    }
+
+    /// The paths of the keys ignored so far, if they are recorded, see [`UnknownKeys`]
+    pub fn ignored_keys(&self) -> Vec<Path> {
+        self.state.ignored_keys.borrow().clone()
+    }
+
+    /// Carry on past invalid values, and fail with every error found rather than the first.
+    ///
+    /// Each invalid value is replaced by a placeholder such as `None`, `0` or an empty string
+    /// where the target type accepts one, so that the rest of the input is still checked. The
+    /// errors are then returned together, see [`PythonizeError::errors`].
+    ///
+    /// Errors raised by the target type itself, such as a missing struct field, cannot be
+    /// replaced and end deserialization once collected. Errors which abort deserialization, such
+    /// as `KeyboardInterrupt` or exceeding `Limits::max_nodes`, are returned alone.
+    pub fn collect_errors(mut self, collect: bool) -> Self {
+        Rc::make_mut(&mut self.state).collect_errors = collect;
+        self
+    }
+
+    /// Emit a `PythonizeWarning` whenever a conversion loses information, with the path of the
+    /// value in its message and `path` attribute.
+    ///
+    /// These conversions are:
+    /// - a `float` narrowed to an `f32` which cannot represent it exactly
+    /// - an object other than a `bool` read as one by its truthiness
+    /// - a value ignored by the target type, unless `UnknownKeys::Warn` already warns about it
+    /// - a `set` or `frozenset` read as a sequence, in arbitrary order. This warns even if the
+    ///   target type is itself a set, which cannot be told apart.
+    ///
+    /// Running Python with `-W error::pythonize.PythonizeWarning` (or with the module the
+    /// classes are registered in, see [`exceptions::register`](crate::exceptions::register))
+    /// then turns these conversions into errors.
+    pub fn warn_lossy(mut self, warn: bool) -> Self {
+        Rc::make_mut(&mut self.state).warn_lossy = warn;
+        self
+    }
+
+    /// Read objects which `deserialize_any` does not support as the substitute returned by `f`,
+    /// like the `default` argument of Python's `json.dumps`.
+    ///
+    /// `f` should return an object which is supported, such as a dict, list, str or number,
+    /// which is then deserialized in place of the original. It is not applied to the substitute
+    /// itself, but is to unsupported objects nested inside it. Errors raised by `f` are returned.
+    ///
+    /// Only objects which would otherwise fail with an "unsupported type" error, or be read as
+    /// maps of their public attributes, are passed to `f`. Dataclasses and attrs classes are still
+    /// read as maps of their fields.
+    pub fn fallback<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> + 'py,
+    {
+        Rc::make_mut(&mut self.state).fallback = Some(Rc::new(f));
+        self
+    }
+
+    /// Use the Python callable `f` as the fallback, see [`Depythonizer::fallback`]
+    pub fn fallback_callable(self, f: Bound<'py, PyAny>) -> Self {
+        self.fallback(move |obj| f.call1((obj,)))
+    }
+
+    /// Read instances of `class` as the substitute returned by `f`, for any target type and
+    /// whether or not `deserialize_any` supports them, such as `pathlib.Path` objects as strings.
+    ///
+    /// This takes precedence over [`Depythonizer::fallback`]. The classes are checked in the
+    /// order they are added, and a substitute is not checked again.
+    pub fn fallback_for<F>(mut self, class: Bound<'py, PyType>, f: F) -> Self
+    where
+        F: Fn(&Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> + 'py,
+    {
+        Rc::make_mut(&mut self.state)
+            .class_fallbacks
+            .push((class, Rc::new(f)));
+        self
+    }
+
+    /// Whether paths are needed, for unknown keys, collected errors or warnings
+    fn tracks_path(&self) -> bool {
+        let state = &self.state;
+        state.unknown_keys != UnknownKeys::Ignore || state.collect_errors || state.warn_lossy
+    }
+
+    /// Deserialize the item at `entry` of the input with `f`, tracking its path if needed and
+    /// adding `entry` to the path of any error
+    fn in_path<T>(&self, entry: PathEntry<'py>, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let result = if !self.tracks_path() {
+            f()
+        } else {
+            self.state.path.borrow_mut().push(entry.clone());
+            let result = f();
+            self.state.path.borrow_mut().pop();
+            result
+        };
+        result.map_err(|e| e.prepend(entry.to_segment()))
+    }
+
+    /// The path of the object being deserialized
+    fn path(&self) -> Path {
+        Path::new(
+            self.state
+                .path
+                .borrow()
+                .iter()
+                .map(PathEntry::to_segment)
+                .collect(),
+        )
+    }
+
+    /// Handle the input being ignored by the target type, as configured by `unknown_keys`
+    fn ignore(&self) -> Result<()> {
+        let path = match self.state.unknown_keys {
+            UnknownKeys::Ignore => return Ok(()),
+            UnknownKeys::Error => return Err(PythonizeError::unknown_key(self.path())),
+            UnknownKeys::Record | UnknownKeys::Warn => self.path(),
+        };
+        if self.state.unknown_keys == UnknownKeys::Warn {
+            self.warn(format!("unknown key {}", path), &path)?;
+        }
+        self.state.ignored_keys.borrow_mut().push(path);
+        Ok(())
+    }
+
+    /// Warn that the input lost information, as described by `what`, if `warn_lossy` is set
+    fn lossy(&self, what: impl FnOnce() -> String) -> Result<()> {
+        if !self.state.warn_lossy {
+            return Ok(());
+        }
+        let path = self.path();
+        let message = if path.segments().is_empty() {
+            what()
+        } else {
+            format!("{} at {}", what(), path)
+        };
+        self.warn(message, &path)
+    }
+
+    /// Emit a `PythonizeWarning` about the value at `path`
+    fn warn(&self, message: String, path: &Path) -> Result<()> {
+        let py = self.input.py();
+        let warning = ExceptionClass::PythonizeWarning
+            .type_object(py)?
+            .call1((message,))?;
+        warning.setattr(intern!(py, "path"), path)?;
+        py.import(intern!(py, "warnings"))?
+            .call_method1(intern!(py, "warn"), (warning,))?;
+        Ok(())
+    }
+
+    /// Deserialize the input with `f`, collecting its error and visiting `placeholder` instead
+    /// if it fails before using the visitor. The outermost call returns the collected errors.
+    fn collect<V, F, P>(&mut self, visitor: V, f: F, placeholder: P) -> Result<V::Value>
+    where
+        V: de::Visitor<'a>,
+        F: for<'v> FnOnce(&mut Self, Recoverable<'v, V>) -> Result<V::Value>,
+        P: FnOnce(V) -> Result<V::Value>,
+    {
+        let state = self.state.clone();
+        state.collect_calls.set(state.collect_calls.get() + 1);
+        let slot = RefCell::new(Some(visitor));
+        let result = f(self, Recoverable::new(&slot));
+
+        let result = match result {
+            Ok(value) => {
+                state.unwinding.set(false);
+                Ok(value)
+            }
+            Err(e) if state.unwinding.get() || self.is_fatal(&e) => Err(e),
+            Err(e) => {
+                let e = self.add_context(e, &slot);
+                state.errors.borrow_mut().push(e.at(self.path()));
+                match slot.into_inner().map(placeholder) {
+                    Some(Ok(value)) => Ok(value),
+                    _ => {
+                        // the error is already collected, so the ones above it are not
+                        state.unwinding.set(true);
+                        Err(PythonizeError::msg("invalid value"))
+                    }
+                }
+            }
+        };
+
+        let calls = state.collect_calls.get() - 1;
+        state.collect_calls.set(calls);
+        if calls > 0 {
+            return result;
+        }
+        state.unwinding.set(false);
+        let errors = state.errors.take();
+        match result {
+            Err(e) if self.is_fatal(&e) => Err(e),
+            _ if !errors.is_empty() => Err(PythonizeError::multiple(errors)),
+            result => result,
+        }
+    }
+
+    /// Deserialize the input with `f`, adding the input and what the visitor expected to errors
+    /// which occur before it is used
+    fn in_context<V, F>(&mut self, visitor: V, f: F) -> Result<V::Value>
+    where
+        V: de::Visitor<'a>,
+        F: for<'v> FnOnce(&mut Self, Recoverable<'v, V>) -> Result<V::Value>,
+    {
+        let slot = RefCell::new(Some(visitor));
+        let result = f(self, Recoverable::new(&slot));
+        result.map_err(|e| self.add_context(e, &slot))
+    }
+
+    /// Describe the input and what the visitor in `slot` expected in `err`, if it needs them
+    fn add_context<V>(&self, err: PythonizeError, slot: &RefCell<Option<V>>) -> PythonizeError
+    where
+        V: de::Visitor<'a>,
+    {
+        match &*slot.borrow() {
+            Some(visitor) if err.needs_context() => {
+                let expected = (visitor as &dyn de::Expected).to_string();
+                err.with_context(truncated_repr(&self.input), expected)
+            }
+            _ => err,
+        }
+    }
+
+    /// Whether `err` must abort deserialization even if errors are being collected
+    fn is_fatal(&self, err: &PythonizeError) -> bool {
+        match err.inner.as_ref() {
+            ErrorImpl::LimitExceeded {
+                limit: Limit::Nodes,
+                ..
+            } => true,
+            ErrorImpl::PyErr(e) => !e.is_instance_of::<PyException>(self.input.py()),
+            _ => false,
+        }
+    }
+
+    /// Count `n` more nested objects, checking the limit and for pending signals
+    fn count_nodes(&self, n: usize) -> Result<()> {
+        let before = self.state.nodes.get();
+        let nodes = before + n;
+        check_limit(Limit::Nodes, self.state.limits.max_nodes, nodes)?;
+        self.state.nodes.set(nodes);
+        let interval = self.state.signal_check_interval;
+        if interval != 0 && nodes / interval != before / interval {
+            self.input.py().check_signals()?;
+        }
+        Ok(())
+    }
+
+    /// Create a deserializer for an object nested inside this deserializer's input
+    fn nested(&self, input: Cow<'a, Bound<'py, PyAny>>) -> Result<Depythonizer<'a, 'py>> {
+        self.count_nodes(1)?;
+        Ok(Depythonizer {
+            input,
+            state: self.state.clone(),
+            depth: self.depth,
+            visiting: None,
+            fields: None,
+            substitute: false,
+        })
+    }
+
+    /// Create a deserializer for the substitute which `fallback_for` gives for the input, if
+    /// its class has a fallback
+    fn class_substitute(&self) -> Result<Option<Depythonizer<'a, 'py>>> {
+        if self.substitute {
+            return Ok(None);
+        }
+        for (class, f) in &self.state.class_fallbacks {
+            if self.input.is_instance(class)? {
+                return self.substitute_with(f.as_ref()).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Deserialize the substitute which `fallback_for` gives for the input with `f`, or else the
+    /// input itself. The substitute is read in place of the input, which is already in context.
+    fn or_class_substitute<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if !self.state.class_fallbacks.is_empty() {
+            if let Some(mut de) = self.class_substitute()? {
+                return f(&mut de);
+            }
+        }
+        f(self)
+    }
+
+    /// Create a deserializer for the substitute which `fallback` gives for the input, if set
+    fn substitute(&self) -> Result<Option<Depythonizer<'a, 'py>>> {
+        match &self.state.fallback {
+            Some(f) if !self.substitute => self.substitute_with(f.as_ref()).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn substitute_with(&self, f: &Fallback<'py>) -> Result<Depythonizer<'a, 'py>> {
+        let mut de = self.nested(Cow::Owned(f(&self.input)?))?;
+        de.substitute = true;
+        Ok(de)
+    }
+
+    /// Create a deserializer for a key of the struct whose fields are being visited
+    fn nested_key(&self, input: Cow<'a, Bound<'py, PyAny>>) -> Result<Depythonizer<'a, 'py>> {
+        let mut de = self.nested(input)?;
+        de.fields = self.fields;
+        Ok(de)
+    }
+
+    /// The Rust name of the field named `key` in Python, if a naming policy applies
+    fn field_name(&self, key: &Bound<'py, PyString>) -> Result<Option<&'static str>> {
+        let state = &self.state;
+        if state.rename_fields.is_none() && !state.case_insensitive {
+            return Ok(None);
+        }
+        let fields = match self.fields {
+            Some(fields) => fields,
+            None => return Ok(None),
+        };
+        Ok(rename::original_name(
+            &key.to_cow()?,
+            fields,
+            |field| match state.rename_fields {
+                Some(rule) => Cow::Owned(rule.apply_to_field(field)),
+                None => Cow::Borrowed(field),
+            },
+            state.case_insensitive,
+        ))
+    }
+
+    /// The Rust name of the variant named `name` in Python, or `name` if none matches
+    fn variant_name<'s>(&self, name: &'s str, variants: &'static [&'static str]) -> &'s str {
+        let state = &self.state;
+        if state.rename_variants.is_none() && !state.case_insensitive {
+            return name;
+        }
+        rename::original_name(
+            name,
+            variants,
+            |variant| match state.rename_variants {
+                Some(rule) => Cow::Owned(rule.apply_to_variant(variant)),
+                None => Cow::Borrowed(variant),
+            },
+            state.case_insensitive,
+        )
+        .unwrap_or(name)
+    }
+
+    fn check_length(&self, len: usize) -> Result<()> {
+        check_limit(Limit::Length, self.state.limits.max_length, len)
+    }
+
+    fn check_str_length(&self, len: usize) -> Result<()> {
+        check_limit(Limit::StrLength, self.state.limits.max_str_length, len)
+    }
+
+    /// Check the input is an instance of `T`, as required by the configured strictness
+    fn check_type<T: PyTypeInfo>(&self) -> Result<()> {
+        let ok = match self.state.strictness {
+            Strictness::Lax => true,
+            Strictness::Strict => self.input.is_instance_of::<T>(),
+            Strictness::Exact => self.input.is_exact_instance_of::<T>(),
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(self.unexpected_type(&T::type_object(self.input.py())))
+        }
+    }
+
+    /// Check the input is a float, as required by the configured strictness
+    fn check_float(&self) -> Result<()> {
+        self.check_type::<PyFloat>()
+    }
+
+    /// Check the input is an integer other than a bool, as required by the configured strictness
+    fn check_int(&self) -> Result<()> {
+        if self.state.strictness != Strictness::Lax && self.input.is_instance_of::<PyBool>() {
+            return Err(self.unexpected_type(&PyInt::type_object(self.input.py())));
+        }
+        self.check_type::<PyInt>()
+    }
+
+    /// In exact mode, check the input is not an instance of a subclass of a builtin container
+    fn check_exact_container(&self) -> Result<()> {
+        if self.state.strictness != Strictness::Exact {
+            return Ok(());
+        }
+        let py = self.input.py();
+        let ty = self.input.get_type();
+        for builtin in [
+            PyDict::type_object(py),
+            PyList::type_object(py),
+            PyTuple::type_object(py),
+            PySet::type_object(py),
+            PyFrozenSet::type_object(py),
+        ] {
+            if !ty.is(&builtin) && ty.is_subclass(&builtin)? {
+                return Err(self.unexpected_type(&builtin));
+            }
+        }
+        Ok(())
+    }
+
+    fn unexpected_type(&self, expected: &Bound<'py, PyType>) -> PythonizeError {
+        PythonizeError::unexpected_type(format_args!(
+            "expected {}, got {}",
+            type_name(expected),
+            type_name(&self.input.get_type())
+        ))
+    }
+
+    /// The input converted to an int as enabled by the configured coercions, if it is not an int
+    fn coerce_int(&self) -> Result<Option<Bound<'py, PyAny>>> {
+        let coercions = &self.state.coercions;
+        let obj = &*self.input;
+        let int = PyInt::type_object(obj.py());
+        if coercions.str_to_number {
+            if let Ok(s) = obj.downcast::<PyString>() {
+                self.check_str_length(s.len()?)?;
+                return Ok(Some(int.call1((obj,))?));
+            }
+        }
+        if coercions.integral_float_to_int {
+            if let Ok(f) = obj.downcast::<PyFloat>() {
+                let f = f.value();
+                if f.is_finite() && f.fract() == 0.0 {
+                    return Ok(Some(int.call1((obj,))?));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The input converted to a float as enabled by the configured coercions, if it is not a
+    /// float
+    fn coerce_float(&self) -> Result<Option<Bound<'py, PyAny>>> {
+        let obj = &*self.input;
+        if self.state.coercions.str_to_number {
+            if let Ok(s) = obj.downcast::<PyString>() {
+                self.check_str_length(s.len()?)?;
+                return Ok(Some(PyFloat::type_object(obj.py()).call1((obj,))?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The input converted to a string as enabled by the configured coercions, if it is not a str
+    fn coerce_str(&self) -> Result<Option<String>> {
+        let coercions = &self.state.coercions;
+        let obj = &*self.input;
+        if coercions.int_to_str && obj.is_instance_of::<PyInt>() && !obj.is_instance_of::<PyBool>()
+        {
+            return Ok(Some(obj.str()?.to_cow()?.into_owned()));
+        }
+        if coercions.bytes_to_str {
+            if let Ok(b) = obj.downcast::<PyBytes>() {
+                self.check_str_length(b.as_bytes().len())?;
+                return match std::str::from_utf8(b.as_bytes()) {
+                    Ok(s) => Ok(Some(s.to_owned())),
+                    Err(_) => Err(de::Error::invalid_value(
+                        de::Unexpected::Bytes(b.as_bytes()),
+                        &"UTF-8 bytes",
+                    )),
+                };
+            }
+        }
+        Ok(None)
+    }
+
+    /// Start visiting the input as a container, one level deeper than this deserializer.
+    ///
+    /// The returned deserializer marks the container as being visited until it is dropped.
+    fn enter(&self) -> Result<Depythonizer<'a, 'py>> {
+        self.check_exact_container()?;
+        let depth = self.depth + 1;
+        if depth > self.state.max_depth {
+            return Err(PythonizeError::depth_limit_exceeded(self.state.max_depth));
+        }
+        let id = self.input.as_ptr() as usize;
+        if self.visiting == Some(id) {
+            // an object tagged by class is entered again to read the contents of its variant
+            return Ok(Depythonizer {
+                input: self.input.clone(),
+                state: self.state.clone(),
+                depth,
+                visiting: None,
+                fields: None,
+                substitute: false,
+            });
+        }
+        if !self.state.visiting.borrow_mut().insert(id) {
+            return Err(PythonizeError::circular_reference());
+        }
+        Ok(Depythonizer {
+            input: self.input.clone(),
+            state: self.state.clone(),
+            depth,
+            visiting: Some(id),
+            fields: None,
+            substitute: false,
+        })
     }
 
     fn sequence_access(&self, expected_len: Option<usize>) -> Result<PySequenceAccess<'a, 'py>> {
         let seq = self.input.downcast::<PySequence>()?;
         let len = self.input.len()?;
+        self.check_length(len)?;
 
         match expected_len {
             Some(expected) if expected != len => {
                 Err(PythonizeError::incorrect_sequence_length(expected, len))
             }
-            _ => Ok(PySequenceAccess::new(seq, len)),
+            _ => Ok(PySequenceAccess::new(
+                seq.clone(),
+                self.borrowed_items(),
+                len,
+                self.enter()?,
+            )),
         }
     }
 
-    fn set_access(&self) -> Result<PySetAsSequence<'py>> {
-        match self.input.downcast::<PySet>() {
-            Ok(set) => Ok(PySetAsSequence::from_set(set)),
-            Err(e) => {
-                if let Ok(f) = self.input.downcast::<PyFrozenSet>() {
-                    Ok(PySetAsSequence::from_frozenset(f))
-                } else {
-                    Err(e.into())
+    /// Access any iterable other than a str, bytes or mapping as a sequence
+    fn iterable_access(&self) -> Result<Option<PyIterableAsSequence<'a, 'py>>> {
+        match self.iterate()? {
+            Some((iter, size_hint)) => Ok(Some(PyIterableAsSequence::new(
+                iter,
+                size_hint,
+                self.enter()?,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Access the items of a mapping as a sequence of `(key, value)` tuples
+    fn items_access(&self, map: &Bound<'py, PyMapping>) -> Result<PyIterableAsSequence<'a, 'py>> {
+        let len = map.len()?;
+        self.check_length(len)?;
+        let iter = map.items()?.try_iter()?;
+        Ok(PyIterableAsSequence::new(iter, Some(len), self.enter()?))
+    }
+
+    /// Access an iterable of `(key, value)` pairs as a map, as accepted by `dict()`
+    fn pairs_access(&self) -> Result<Option<PyPairsAsMapping<'a, 'py>>> {
+        match self.iterate()? {
+            Some((iter, size_hint)) => Ok(Some(PyPairsAsMapping {
+                inner: PyIterableAsSequence::new(iter, size_hint, self.enter()?),
+                pair: None,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Iterate over any iterable other than a str, bytes or mapping, with its estimated length
+    fn iterate(&self) -> Result<Option<IteratorWithHint<'py>>> {
+        let obj = &*self.input;
+        if obj.is_instance_of::<PyString>()
+            || obj.is_instance_of::<PyBytes>()
+            || obj.is_instance_of::<PyByteArray>()
+            || obj.downcast::<PyMapping>().is_ok()
+        {
+            return Ok(None);
+        }
+        let iter = match obj.try_iter() {
+            Ok(iter) => iter,
+            Err(e) if e.is_instance_of::<PyTypeError>(obj.py()) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let len = obj.len().ok();
+        if let Some(len) = len {
+            self.check_length(len)?;
+        }
+        Ok(Some((iter, len.or_else(|| length_hint(obj)))))
+    }
+
+    fn dict_access(&self) -> Result<PyMappingAccess<'a, 'py>> {
+        let map = self.input.downcast::<PyMapping>()?;
+        self.check_length(map.len()?)?;
+        PyMappingAccess::new(map, self.enter()?)
+    }
+
+    /// Access the given attributes of the input as a map, skipping attributes which are not set
+    fn attribute_access(
+        &self,
+        names: Vec<Bound<'py, PyString>>,
+    ) -> Result<PyAttributeAccess<'a, 'py>> {
+        let mut items = Vec::with_capacity(names.len());
+        for name in names {
+            match self.input.getattr(&name) {
+                Ok(value) => items.push((name, value)),
+                Err(e) if e.is_instance_of::<PyAttributeError>(self.input.py()) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.check_length(items.len())?;
+        Ok(PyAttributeAccess::new(items, self.enter()?))
+    }
+
+    /// The names of the attributes to deserialize when the input is an attribute-based object,
+    /// such as a dataclass, an attrs class, a `SimpleNamespace` or an instance with public
+    /// attributes in `__slots__` or `__dict__`
+    fn attribute_names(&self) -> Result<Option<Vec<Bound<'py, PyString>>>> {
+        match self.field_names()? {
+            Some(names) => Ok(Some(names)),
+            None => self.public_attribute_names(),
+        }
+    }
+
+    /// The names of the fields declared by the class of the input, if it is a dataclass or an
+    /// attrs class
+    fn field_names(&self) -> Result<Option<Vec<Bound<'py, PyString>>>> {
+        let obj = &*self.input;
+        let py = obj.py();
+        let ty = obj.get_type();
+        let names_of = |fields: Bound<'py, PyAny>| -> Result<Option<Vec<Bound<'py, PyString>>>> {
+            fields
+                .try_iter()?
+                .map(|field| Ok(field?.getattr(intern!(py, "name"))?.downcast_into()?))
+                .collect::<Result<_>>()
+                .map(Some)
+        };
+
+        if ty.hasattr(intern!(py, "__dataclass_fields__"))? {
+            let dataclasses = py.import(intern!(py, "dataclasses"))?;
+            return names_of(dataclasses.call_method1(intern!(py, "fields"), (obj,))?);
+        }
+        if let Ok(fields) = ty.getattr(intern!(py, "__attrs_attrs__")) {
+            return names_of(fields);
+        }
+        Ok(None)
+    }
+
+    /// The names of the public attributes of the input in `__slots__` or `__dict__`, if it looks
+    /// like a record of data
+    fn public_attribute_names(&self) -> Result<Option<Vec<Bound<'py, PyString>>>> {
+        let obj = &*self.input;
+        let py = obj.py();
+        let ty = obj.get_type();
+        // classes, modules, functions, exceptions and enum members have attributes, but aren't
+        // records of data
+        if obj.is_instance_of::<PyType>()
+            || obj.is_instance_of::<PyModule>()
+            || obj.is_callable()
+            || obj.is_instance_of::<PyBaseException>()
+            || obj.is_instance(
+                &py.import(intern!(py, "enum"))?
+                    .getattr(intern!(py, "Enum"))?,
+            )?
+        {
+            return Ok(None);
+        }
+        let namespace_type = py
+            .import(intern!(py, "types"))?
+            .getattr(intern!(py, "SimpleNamespace"))?;
+
+        // private names are implementation details, such as the parts of a `pathlib` path
+        let is_public = |name: &Bound<'py, PyString>| -> PyResult<bool> {
+            Ok(!name.to_cow()?.starts_with('_'))
+        };
+        let mut names = Vec::new();
+        for cls in ty.mro().iter() {
+            let namespace = cls.getattr(intern!(py, "__dict__"))?;
+            if !namespace.contains(intern!(py, "__slots__"))? {
+                continue;
+            }
+            let slots = namespace.get_item(intern!(py, "__slots__"))?;
+            let slots = match slots.downcast_into::<PyString>() {
+                Ok(slot) => vec![slot.into_any()],
+                Err(e) => e.into_inner().try_iter()?.collect::<PyResult<_>>()?,
+            };
+            for slot in slots {
+                let slot = slot.downcast_into::<PyString>()?;
+                if is_public(&slot)? {
+                    names.push(slot);
                 }
             }
         }
+        if let Ok(dict) = obj.getattr(intern!(py, "__dict__")) {
+            if let Ok(dict) = dict.downcast::<PyDict>() {
+                for key in dict.keys() {
+                    if let Ok(key) = key.downcast_into::<PyString>() {
+                        if is_public(&key)? {
+                            names.push(key);
+                        }
+                    }
+                }
+            }
+        }
+        // an object without public attributes is more likely opaque than an empty record
+        if names.is_empty() && !obj.is_instance(&namespace_type)? {
+            return Ok(None);
+        }
+        Ok(Some(names))
     }
 
-    fn dict_access(&self) -> Result<PyMappingAccess<'py>> {
-        PyMappingAccess::new(self.input.downcast()?)
+    /// Deserialize a struct from a mapping, from a sequence of the field values in order,
+    /// or from the attributes of any other object
+    fn deserialize_struct_fields<V>(
+        &self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'a>,
+    {
+        let obj = &*self.input;
+        if obj.downcast::<PyMapping>().is_ok() {
+            let mut map = self.dict_access()?;
+            map.de.fields = Some(fields);
+            return visitor.visit_map(map);
+        }
+        // namedtuples are read by field name rather than by position
+        if let Some(names) = namedtuple_fields(obj)? {
+            let mut attributes = self.attribute_access(names)?;
+            attributes.de.fields = Some(fields);
+            return visitor.visit_map(attributes);
+        }
+        if obj.downcast::<PySequence>().is_ok()
+            && !obj.is_instance_of::<PyString>()
+            && !obj.is_instance_of::<PyBytes>()
+            && !obj.is_instance_of::<PyByteArray>()
+        {
+            let len = obj.len()?;
+            if len > fields.len() {
+                return Err(PythonizeError::incorrect_sequence_length(fields.len(), len));
+            }
+            // the values are matched with the fields by position, and `None` is read as a
+            // missing field: `Pythonizer` writes it for fields skipped with
+            // `skip_serializing_if`, which may only accept their `#[serde(default)]`
+            self.check_length(len)?;
+            let py = obj.py();
+            let mut items = Vec::with_capacity(len);
+            for (field, item) in fields.iter().zip(obj.try_iter()?) {
+                let item = item?;
+                if !item.is_none() {
+                    items.push((PyString::new(py, field), item));
+                }
+            }
+            return visitor.visit_map(PyAttributeAccess::new(items, self.enter()?));
+        }
+        if is_builtin_value(obj) {
+            return visitor.visit_map(self.dict_access()?);
+        }
+        let py = obj.py();
+        let rule = self.state.rename_fields;
+        let names = fields
+            .iter()
+            .map(|field| match rule {
+                Some(rule) => PyString::new(py, &rule.apply_to_field(field)),
+                None => PyString::new(py, field),
+            })
+            .collect();
+        let mut attributes = self.attribute_access(names)?;
+        attributes.de.fields = Some(fields);
+        visitor.visit_map(attributes)
+    }
+
+    /// Read the enum variant of the input as described by `tag`
+    fn class_variant(
+        &self,
+        tag: &ClassTag,
+        variants: &'static [&'static str],
+    ) -> Result<Bound<'py, PyString>> {
+        match tag {
+            ClassTag::ClassName => {
+                let ty = self.input.get_type();
+                for cls in ty.mro().iter() {
+                    let name = cls.downcast_into::<PyType>()?.name()?;
+                    if variants.contains(&&*name.to_cow()?) {
+                        return Ok(name);
+                    }
+                }
+                // Not a variant, which is reported as an unknown variant
+                Ok(ty.name()?)
+            }
+            ClassTag::Attribute(attr) => Ok(self.input.getattr(attr.as_str())?.downcast_into()?),
+        }
+    }
+
+    /// Deserialize a buffer of numbers as a sequence, without creating a Python object per item
+    #[cfg(any(not(Py_LIMITED_API), Py_3_11))]
+    fn visit_buffer<'de, T, V>(&self, buffer: PyBuffer<T>, visitor: V) -> Result<V::Value>
+    where
+        T: Element + IntoDeserializer<'de, PythonizeError>,
+        V: de::Visitor<'de>,
+    {
+        let _de = self.enter()?;
+        let len = buffer.item_count();
+        self.check_length(len)?;
+        self.count_nodes(len)?;
+        let items = buffer.to_vec(self.input.py())?;
+        visitor.visit_seq(de::value::SeqDeserializer::new(items.into_iter()))
+    }
+
+    /// Deserialize a [`Shared`](crate::Shared) value, reusing the result for repeated objects
+    fn deserialize_shared<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'a>,
+    {
+        let id = self.input.as_ptr() as usize;
+        let cached = self.state.shared.borrow().get(&id).map(|(_, v)| v.clone());
+        shared::set_de_slot(match cached {
+            Some(value) => DeSlot::Hit(value),
+            None => DeSlot::Miss,
+        });
+        let value = visitor.visit_newtype_struct(&mut *self)?;
+        if let Some(DeSlot::Stored(stored)) = shared::take_de_slot() {
+            self.state
+                .shared
+                .borrow_mut()
+                .insert(id, (Bound::clone(&self.input), stored));
+        }
+        Ok(value)
+    }
+
+    /// Visit a str, borrowing it from the input for `'a` when possible
+    fn visit_str<V>(&self, s: &Bound<'py, PyString>, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'a>,
+    {
+        // CPython caches the UTF-8 encoding of a str, which lives as long as the str itself
+        #[cfg(any(Py_3_10, not(Py_LIMITED_API)))]
+        if let Cow::Borrowed(obj) = self.input {
+            return visitor.visit_borrowed_str(obj.downcast::<PyString>()?.to_str()?);
+        }
+        visitor.visit_str(&s.to_cow()?)
+    }
+
+    /// The items of the input if it is a tuple borrowed for `'a`
+    fn borrowed_items(&self) -> Option<&'a [Bound<'py, PyAny>]> {
+        #[cfg(not(any(Py_LIMITED_API, GraalPy)))]
+        if let Cow::Borrowed(obj) = self.input {
+            return obj.downcast::<PyTuple>().ok().map(|t| t.as_slice());
+        }
+        None
+    }
+
+    /// The value of an integer-like input as an int, following `operator.index`, if it
+    /// implements `__index__` and strictness is lax
+    fn number_index(&self) -> Result<Option<Bound<'py, PyInt>>> {
+        if self.state.strictness != Strictness::Lax {
+            return Ok(None);
+        }
+        // `PyIndex_Check` is only part of the limited API since Python 3.8
+        #[cfg(any(not(Py_LIMITED_API), Py_3_8))]
+        let is_index = unsafe { ffi::PyIndex_Check(self.input.as_ptr()) } != 0;
+        #[cfg(all(Py_LIMITED_API, not(Py_3_8)))]
+        let is_index = self
+            .input
+            .get_type()
+            .hasattr(intern!(self.input.py(), "__index__"))?;
+        if !is_index {
+            return Ok(None);
+        }
+        let py = self.input.py();
+        match unsafe { Bound::from_owned_ptr_or_err(py, ffi::PyNumber_Index(self.input.as_ptr())) }
+        {
+            Ok(index) => Ok(Some(index.downcast_into()?)),
+            // `__index__` may refuse the value, like NumPy arrays which aren't scalars
+            Err(e) if e.is_instance_of::<PyTypeError>(py) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
     }
 
     fn deserialize_any_int<'de, V>(&self, int: &Bound<'_, PyInt>, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if let Some(max_bits) = self.state.limits.max_int_bits {
+            let bits: usize = int.call_method0("bit_length")?.extract()?;
+            check_limit(Limit::IntBits, Some(max_bits), bits)?;
+        }
+
         if let Ok(x) = int.extract::<u128>() {
-            if let Ok(x) = u8::try_from(x) {
-                visitor.visit_u8(x)
-            } else if let Ok(x) = u16::try_from(x) {
-                visitor.visit_u16(x)
-            } else if let Ok(x) = u32::try_from(x) {
-                visitor.visit_u32(x)
-            } else if let Ok(x) = u64::try_from(x) {
-                visitor.visit_u64(x)
-            } else {
-                visitor.visit_u128(x)
-            }
+            visit_unsigned(x, visitor)
         } else {
-            let x: i128 = int.extract()?;
-            if let Ok(x) = i8::try_from(x) {
-                visitor.visit_i8(x)
-            } else if let Ok(x) = i16::try_from(x) {
-                visitor.visit_i16(x)
-            } else if let Ok(x) = i32::try_from(x) {
-                visitor.visit_i32(x)
-            } else if let Ok(x) = i64::try_from(x) {
-                visitor.visit_i64(x)
-            } else {
-                visitor.visit_i128(x)
-            }
+            visit_signed(int.extract()?, visitor)
         }
     }
 }
 
+/// Used by `collect_errors!` for values which have no placeholder
+fn no_placeholder<T, V>(_visitor: V) -> Result<T> {
+    Err(PythonizeError::msg("no placeholder"))
+}
+
+/// Visit an unsigned integer using the smallest type which can represent it
+pub(crate) fn visit_unsigned<'de, V>(x: u128, visitor: V) -> Result<V::Value>
+where
+    V: de::Visitor<'de>,
+{
+    if let Ok(x) = u8::try_from(x) {
+        visitor.visit_u8(x)
+    } else if let Ok(x) = u16::try_from(x) {
+        visitor.visit_u16(x)
+    } else if let Ok(x) = u32::try_from(x) {
+        visitor.visit_u32(x)
+    } else if let Ok(x) = u64::try_from(x) {
+        visitor.visit_u64(x)
+    } else {
+        visitor.visit_u128(x)
+    }
+}
+
+/// Visit a signed integer using the smallest type which can represent it
+pub(crate) fn visit_signed<'de, V>(x: i128, visitor: V) -> Result<V::Value>
+where
+    V: de::Visitor<'de>,
+{
+    if let Ok(x) = i8::try_from(x) {
+        visitor.visit_i8(x)
+    } else if let Ok(x) = i16::try_from(x) {
+        visitor.visit_i16(x)
+    } else if let Ok(x) = i32::try_from(x) {
+        visitor.visit_i32(x)
+    } else if let Ok(x) = i64::try_from(x) {
+        visitor.visit_i64(x)
+    } else {
+        visitor.visit_i128(x)
+    }
+}
+
+/// The qualified name of a type, for error messages
+fn type_name(ty: &Bound<'_, PyType>) -> String {
+    ty.qualname()
+        .map_or_else(|_| "unknown".to_string(), |name| name.to_string())
+}
+
+/// Whether `obj` is an instance of a built-in type which is never read by attribute
+fn is_builtin_value(obj: &Bound<'_, PyAny>) -> bool {
+    obj.is_none()
+        || obj.is_instance_of::<PyBool>()
+        || obj.is_instance_of::<PyInt>()
+        || obj.is_instance_of::<PyFloat>()
+        || obj.is_instance_of::<PyString>()
+        || obj.is_instance_of::<PyBytes>()
+        || obj.is_instance_of::<PyByteArray>()
+        || obj.is_instance_of::<PyList>()
+        || obj.is_exact_instance_of::<PyTuple>()
+        || obj.is_instance_of::<PySet>()
+        || obj.is_instance_of::<PyFrozenSet>()
+}
+
+/// Whether `obj` is a str, int, float or bool
+fn is_scalar(obj: &Bound<'_, PyAny>) -> bool {
+    obj.is_instance_of::<PyString>()
+        || obj.is_instance_of::<PyInt>()
+        || obj.is_instance_of::<PyFloat>()
+}
+
+/// The `repr()` of `obj` for error messages, shortened if it is long
+fn truncated_repr(obj: &Bound<'_, PyAny>) -> String {
+    const MAX_CHARS: usize = 60;
+    // `reprlib` stops after a few items of each container instead of building the whole repr
+    static REPR: GILOnceCell<Py<PyAny>> = GILOnceCell::new();
+    let py = obj.py();
+    let repr = REPR
+        .get_or_try_init(py, || -> PyResult<_> {
+            let repr = py.import("reprlib")?.getattr("Repr")?.call0()?;
+            repr.setattr("maxlevel", 3)?;
+            repr.setattr("maxstring", MAX_CHARS)?;
+            repr.setattr("maxlong", MAX_CHARS)?;
+            repr.setattr("maxother", MAX_CHARS)?;
+            Ok(repr.unbind())
+        })
+        .and_then(|repr| repr.bind(py).call_method1("repr", (obj,))?.extract());
+    let repr: String = match repr {
+        Ok(repr) => repr,
+        Err(_) => return String::from("<unprintable object>"),
+    };
+    if repr.chars().count() <= MAX_CHARS {
+        return repr;
+    }
+    let mut truncated: String = repr.chars().take(MAX_CHARS - 3).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+/// The field names of a `collections.namedtuple` or `typing.NamedTuple` instance
+fn namedtuple_fields<'py>(obj: &Bound<'py, PyAny>) -> Result<Option<Vec<Bound<'py, PyString>>>> {
+    if obj.is_exact_instance_of::<PyTuple>() || !obj.is_instance_of::<PyTuple>() {
+        return Ok(None);
+    }
+    match obj.get_type().getattr(intern!(obj.py(), "_fields")) {
+        Ok(fields) => Ok(Some(
+            fields
+                .try_iter()?
+                .map(|field| Ok(field?.downcast_into()?))
+                .collect::<Result<_>>()?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+/// The estimated length of an iterable, if it provides a `__length_hint__`
+fn length_hint(obj: &Bound<'_, PyAny>) -> Option<usize> {
+    obj.call_method0(intern!(obj.py(), "__length_hint__"))
+        .ok()?
+        .extract()
+        .ok()
+}
+
+/// The type of the items of a one-dimensional buffer, if `obj` supports the buffer protocol
+#[cfg(any(not(Py_LIMITED_API), Py_3_11))]
+fn buffer_element_type(obj: &Bound<'_, PyAny>) -> Option<ElementType> {
+    // lists and tuples are the most common sequences, and never buffers
+    if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
+        return None;
+    }
+    let view = PyMemoryView::from(obj).ok()?;
+    let py = obj.py();
+    if view
+        .getattr(intern!(py, "ndim"))
+        .ok()?
+        .extract::<usize>()
+        .ok()?
+        != 1
+    {
+        return None;
+    }
+    let format: String = view.getattr(intern!(py, "format")).ok()?.extract().ok()?;
+    Some(ElementType::from_format(&CString::new(format).ok()?))
+}
+
+fn check_limit(limit: Limit, max: Option<usize>, got: usize) -> Result<()> {
+    match max {
+        Some(max) if got > max => Err(PythonizeError::limit_exceeded(limit, max, got)),
+        _ => Ok(()),
+    }
+}
+
 macro_rules! deserialize_type {
-    ($method:ident => $visit:ident) => {
+    ($method:ident => $visit:ident, $check:ident, $coerce:ident) => {
         fn $method<V>(self, visitor: V) -> Result<V::Value>
         where
             V: de::Visitor<'de>,
         {
+            if let Some(obj) = self.$coerce()? {
+                return visitor.$visit(obj.extract()?);
+            }
+            self.$check()?;
             visitor.$visit(self.input.extract()?)
         }
     };
 }
 
-impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
+impl Drop for Depythonizer<'_, '_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.visiting {
+            self.state.visiting.borrow_mut().remove(&id);
+        }
+    }
+}
+
+/// Deserialize the input of a `Depythonizer`, with a placeholder to visit instead if collecting
+/// errors, see `Depythonizer::collect`
+macro_rules! collect_errors {
+    ($($method:ident($($arg:ident: $ty:ty),*) => $placeholder:expr;)*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                if self.state.collect_errors {
+                    self.collect(
+                        visitor,
+                        |de, v| de.or_class_substitute(|de| Direct(de).$method($($arg,)* v)),
+                        $placeholder,
+                    )
+                } else {
+                    self.in_context(visitor, |de, v| {
+                        de.or_class_substitute(|de| Direct(de).$method($($arg,)* v))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'de, '_> {
+    type Error = PythonizeError;
+
+    collect_errors! {
+        deserialize_any() => |v| v.visit_unit();
+        deserialize_bool() => |v| v.visit_bool(false);
+        deserialize_i8() => |v| v.visit_i8(0);
+        deserialize_i16() => |v| v.visit_i16(0);
+        deserialize_i32() => |v| v.visit_i32(0);
+        deserialize_i64() => |v| v.visit_i64(0);
+        deserialize_i128() => |v| v.visit_i128(0);
+        deserialize_u8() => |v| v.visit_u8(0);
+        deserialize_u16() => |v| v.visit_u16(0);
+        deserialize_u32() => |v| v.visit_u32(0);
+        deserialize_u64() => |v| v.visit_u64(0);
+        deserialize_u128() => |v| v.visit_u128(0);
+        deserialize_f32() => |v| v.visit_f32(0.0);
+        deserialize_f64() => |v| v.visit_f64(0.0);
+        deserialize_char() => |v| v.visit_char('\0');
+        deserialize_str() => |v| v.visit_str("");
+        deserialize_string() => |v| v.visit_str("");
+        deserialize_bytes() => |v| v.visit_bytes(&[]);
+        deserialize_byte_buf() => |v| v.visit_bytes(&[]);
+        deserialize_option() => |v| v.visit_none();
+        deserialize_unit() => |v| v.visit_unit();
+        deserialize_unit_struct(name: &'static str) => |v| v.visit_unit();
+        deserialize_newtype_struct(name: &'static str) => no_placeholder;
+        deserialize_seq() => |v| v.visit_seq(collect::empty_seq());
+        deserialize_tuple(len: usize) => |v| v.visit_seq(collect::empty_seq());
+        deserialize_tuple_struct(name: &'static str, len: usize) => |v| v.visit_seq(collect::empty_seq());
+        deserialize_map() => |v| v.visit_map(collect::empty_map());
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]) => |v| v.visit_map(collect::empty_map());
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]) => no_placeholder;
+        deserialize_identifier() => |v| v.visit_str("");
+        deserialize_ignored_any() => |v| v.visit_unit();
+    }
+}
+
+/// Deserializes the input of a `Depythonizer` itself, without collecting errors
+struct Direct<'s, 'a, 'py>(&'s mut Depythonizer<'a, 'py>);
+
+impl<'a, 'py> Deref for Direct<'_, 'a, 'py> {
+    type Target = Depythonizer<'a, 'py>;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl DerefMut for Direct<'_, '_, '_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Direct<'_, 'de, '_> {
     type Error = PythonizeError;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        let obj = self.input;
+        let obj = &*self.input;
 
         // First check for cases which are cheap to check due to pointer
         // comparison or bitflag checks
@@ -112,9 +1522,16 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
         } else if obj.is_instance_of::<PyBool>() {
             self.deserialize_bool(visitor)
         } else if let Ok(x) = obj.downcast::<PyInt>() {
+            self.check_int()?;
             self.deserialize_any_int(x, visitor)
         } else if obj.is_instance_of::<PyList>() || obj.is_instance_of::<PyTuple>() {
-            self.deserialize_tuple(obj.len()?, visitor)
+            match namedtuple_fields(obj)? {
+                Some(fields) => visitor.visit_map(self.attribute_access(fields)?),
+                None => {
+                    let len = obj.len()?;
+                    self.deserialize_tuple(len, visitor)
+                }
+            }
         } else if obj.is_instance_of::<PyDict>() {
             self.deserialize_map(visitor)
         } else if obj.is_instance_of::<PyString>() {
@@ -129,9 +1546,30 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
         } else if obj.is_instance_of::<PyFrozenSet>() || obj.is_instance_of::<PySet>() {
             self.deserialize_seq(visitor)
         } else if obj.downcast::<PySequence>().is_ok() {
-            self.deserialize_tuple(obj.len()?, visitor)
+            let len = obj.len()?;
+            self.deserialize_tuple(len, visitor)
         } else if obj.downcast::<PyMapping>().is_ok() {
             self.deserialize_map(visitor)
+        }
+        // Numbers which are not int or float, such as numpy scalars or `Decimal`
+        else if let Some(x) = self.number_index()? {
+            self.deserialize_any_int(&x, visitor)
+        } else if let Some(iter) = self.iterable_access()? {
+            visitor.visit_seq(iter)
+        }
+        // after iterables, as `__float__` is also implemented by containers such as NumPy arrays
+        else if self.state.strictness == Strictness::Lax
+            && obj.get_type().hasattr(intern!(obj.py(), "__float__"))?
+        {
+            self.deserialize_f64(visitor)
+        } else if let Some(names) = self.field_names()? {
+            visitor.visit_map(self.attribute_access(names)?)
+        }
+        // other objects with attributes may be opaque, so the fallback decides how to read them
+        else if let Some(mut de) = self.substitute()? {
+            Direct(&mut de).deserialize_any(visitor)
+        } else if let Some(names) = self.public_attribute_names()? {
+            visitor.visit_map(self.attribute_access(names)?)
         } else {
             Err(obj.get_type().qualname().map_or_else(
                 |_| PythonizeError::unsupported_type("unknown"),
@@ -144,6 +1582,30 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
     where
         V: de::Visitor<'de>,
     {
+        if self.state.coercions.str_to_bool {
+            if let Ok(s) = self.input.downcast::<PyString>() {
+                let s = s.to_cow()?;
+                return if s.eq_ignore_ascii_case("true") {
+                    visitor.visit_bool(true)
+                } else if s.eq_ignore_ascii_case("false") {
+                    visitor.visit_bool(false)
+                } else {
+                    Err(de::Error::invalid_value(
+                        de::Unexpected::Str(&s),
+                        &"\"true\" or \"false\"",
+                    ))
+                };
+            }
+        }
+        self.check_type::<PyBool>()?;
+        if !self.input.is_instance_of::<PyBool>() {
+            self.lossy(|| {
+                format!(
+                    "{} converted to bool by truthiness",
+                    type_name(&self.input.get_type())
+                )
+            })?;
+        }
         visitor.visit_bool(self.input.is_truthy()?)
     }
 
@@ -151,6 +1613,7 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
     where
         V: de::Visitor<'de>,
     {
+        self.check_type::<PyString>()?;
         let s = self.input.downcast::<PyString>()?.to_cow()?;
         if s.len() != 1 {
             return Err(PythonizeError::invalid_length_char());
@@ -158,31 +1621,57 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
         visitor.visit_char(s.chars().next().unwrap())
     }
 
-    deserialize_type!(deserialize_i8 => visit_i8);
-    deserialize_type!(deserialize_i16 => visit_i16);
-    deserialize_type!(deserialize_i32 => visit_i32);
-    deserialize_type!(deserialize_i64 => visit_i64);
-    deserialize_type!(deserialize_i128 => visit_i128);
-    deserialize_type!(deserialize_u8 => visit_u8);
-    deserialize_type!(deserialize_u16 => visit_u16);
-    deserialize_type!(deserialize_u32 => visit_u32);
-    deserialize_type!(deserialize_u64 => visit_u64);
-    deserialize_type!(deserialize_u128 => visit_u128);
-    deserialize_type!(deserialize_f32 => visit_f32);
-    deserialize_type!(deserialize_f64 => visit_f64);
+    deserialize_type!(deserialize_i8 => visit_i8, check_int, coerce_int);
+    deserialize_type!(deserialize_i16 => visit_i16, check_int, coerce_int);
+    deserialize_type!(deserialize_i32 => visit_i32, check_int, coerce_int);
+    deserialize_type!(deserialize_i64 => visit_i64, check_int, coerce_int);
+    deserialize_type!(deserialize_i128 => visit_i128, check_int, coerce_int);
+    deserialize_type!(deserialize_u8 => visit_u8, check_int, coerce_int);
+    deserialize_type!(deserialize_u16 => visit_u16, check_int, coerce_int);
+    deserialize_type!(deserialize_u32 => visit_u32, check_int, coerce_int);
+    deserialize_type!(deserialize_u64 => visit_u64, check_int, coerce_int);
+    deserialize_type!(deserialize_u128 => visit_u128, check_int, coerce_int);
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let x: f64 = match self.coerce_float()? {
+            Some(obj) => obj.extract()?,
+            None => {
+                self.check_float()?;
+                self.input.extract()?
+            }
+        };
+        let narrowed = x as f32;
+        if f64::from(narrowed) != x && !x.is_nan() {
+            self.lossy(|| format!("float {} rounded to {} as f32", x, f64::from(narrowed)))?;
+        }
+        visitor.visit_f32(narrowed)
+    }
+
+    deserialize_type!(deserialize_f64 => visit_f64, check_float, coerce_float);
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if let Some(s) = self.coerce_str()? {
+            return visitor.visit_string(s);
+        }
         let s = self.input.downcast::<PyString>()?;
-        visitor.visit_str(&s.to_cow()?)
+        self.check_type::<PyString>()?;
+        self.check_str_length(s.len()?)?;
+        self.visit_str(s, visitor)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if self.state.validating {
+            return self.deserialize_str(SkipContents(visitor));
+        }
         self.deserialize_str(visitor)
     }
 
@@ -190,14 +1679,41 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
     where
         V: de::Visitor<'de>,
     {
-        let b = self.input.downcast::<PyBytes>()?;
-        visitor.visit_bytes(b.as_bytes())
+        let obj = &*self.input;
+        let b = match obj.downcast::<PyBytes>() {
+            Ok(b) => b,
+            Err(e) => {
+                // only immutable `bytes` can be borrowed: `bytearray` and other buffers may be
+                // resized or written while the result is alive, so they are always copied and
+                // `&[u8]` cannot be deserialized from them
+                if let Ok(b) = obj.downcast::<PyByteArray>() {
+                    self.check_str_length(b.len())?;
+                    return visitor.visit_byte_buf(b.to_vec());
+                }
+                // any other object supporting the buffer protocol, such as `memoryview`
+                let view = PyMemoryView::from(obj).map_err(|_| e)?;
+                let len = view.getattr(intern!(obj.py(), "nbytes"))?.extract()?;
+                self.check_str_length(len)?;
+                let b = view.call_method0(intern!(obj.py(), "tobytes"))?;
+                return visitor.visit_bytes(b.downcast::<PyBytes>()?.as_bytes());
+            }
+        };
+        self.check_str_length(b.as_bytes().len())?;
+        match self.input {
+            Cow::Borrowed(obj) => {
+                visitor.visit_borrowed_bytes(obj.downcast::<PyBytes>()?.as_bytes())
+            }
+            Cow::Owned(_) => visitor.visit_bytes(b.as_bytes()),
+        }
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if self.state.validating {
+            return self.deserialize_bytes(SkipContents(visitor));
+        }
         self.deserialize_bytes(visitor)
     }
 
@@ -208,7 +1724,7 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
         if self.input.is_none() {
             visitor.visit_none()
         } else {
-            visitor.visit_some(self)
+            visitor.visit_some(self.0)
         }
     }
 
@@ -230,24 +1746,77 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(mut self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name == SHARED_TOKEN {
+            return self.deserialize_shared(visitor);
+        }
+        if name == PYBACKED_TOKEN {
+            if let Ok(s) = self.input.downcast::<PyString>() {
+                self.check_str_length(s.len()?)?;
+            } else if let Ok(b) = self.input.downcast::<PyBytes>() {
+                self.check_str_length(b.len()?)?;
+            }
+            pybacked::set_de_slot(Bound::clone(&self.input).unbind());
+        }
+        visitor.visit_newtype_struct(self.0)
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if self.input.is_instance_of::<PySet>() || self.input.is_instance_of::<PyFrozenSet>() {
+            self.lossy(|| {
+                format!(
+                    "{} read as a sequence in arbitrary order",
+                    type_name(&self.input.get_type())
+                )
+            })?;
+        }
+        if self.state.coercions.scalar_to_seq && is_scalar(&self.input) {
+            return visitor.visit_seq(PyScalarAsSequence {
+                de: Some(self.nested(self.input.clone())?),
+            });
+        }
+
+        #[cfg(any(not(Py_LIMITED_API), Py_3_11))]
+        if let Some(element_type) = buffer_element_type(&self.input) {
+            macro_rules! visit_buffer {
+                ($ty:ty) => {
+                    if let Ok(buffer) = PyBuffer::<$ty>::get(&self.input) {
+                        return self.visit_buffer(buffer, visitor);
+                    }
+                };
+            }
+            match element_type {
+                ElementType::SignedInteger { bytes: 1 } => visit_buffer!(i8),
+                ElementType::SignedInteger { bytes: 2 } => visit_buffer!(i16),
+                ElementType::SignedInteger { bytes: 4 } => visit_buffer!(i32),
+                ElementType::SignedInteger { bytes: 8 } => visit_buffer!(i64),
+                ElementType::UnsignedInteger { bytes: 1 } => visit_buffer!(u8),
+                ElementType::UnsignedInteger { bytes: 2 } => visit_buffer!(u16),
+                ElementType::UnsignedInteger { bytes: 4 } => visit_buffer!(u32),
+                ElementType::UnsignedInteger { bytes: 8 } => visit_buffer!(u64),
+                ElementType::Float { bytes: 4 } => visit_buffer!(f32),
+                ElementType::Float { bytes: 8 } => visit_buffer!(f64),
+                // other items are read one by one below
+                _ => {}
+            }
+        }
+
         match self.sequence_access(None) {
             Ok(seq) => visitor.visit_seq(seq),
             Err(e) => {
-                // we allow sets to be deserialized as sequences, so try that
+                // we allow sets and other iterables to be deserialized as sequences, so try that
                 if matches!(*e.inner, ErrorImpl::UnexpectedType(_)) {
-                    if let Ok(set) = self.set_access() {
-                        return visitor.visit_seq(set);
+                    if let Ok(map) = self.input.downcast::<PyMapping>() {
+                        return visitor.visit_seq(self.items_access(map)?);
+                    }
+                    if let Some(iter) = self.iterable_access()? {
+                        return visitor.visit_seq(iter);
                     }
                 }
                 Err(e)
@@ -278,25 +1847,36 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_map(self.dict_access()?)
+        match self.dict_access() {
+            Ok(map) => visitor.visit_map(map),
+            Err(e) => {
+                // we allow iterables of pairs to be deserialized as maps, like `dict()` does
+                if matches!(*e.inner, ErrorImpl::UnexpectedType(_)) {
+                    if let Some(pairs) = self.pairs_access()? {
+                        return visitor.visit_map(pairs);
+                    }
+                }
+                Err(e)
+            }
+        }
     }
 
     fn deserialize_struct<V>(
         self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        self.deserialize_struct_fields(fields, visitor)
     }
 
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
@@ -304,7 +1884,10 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
     {
         let item = &self.input;
         if let Ok(s) = item.downcast::<PyString>() {
-            visitor.visit_enum(s.to_cow()?.into_deserializer())
+            self.check_type::<PyString>()?;
+            self.check_str_length(s.len()?)?;
+            let name = s.to_cow()?;
+            visitor.visit_enum(self.variant_name(&name, variants).into_deserializer())
         } else if let Ok(m) = item.downcast::<PyMapping>() {
             // Get the enum variant from the mapping key
             if m.len()? != 1 {
@@ -315,8 +1898,23 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
                 .get_item(0)?
                 .downcast_into::<PyString>()
                 .map_err(|_| PythonizeError::dict_key_not_string())?;
+            self.check_str_length(variant.len()?)?;
             let value = m.get_item(&variant)?;
-            visitor.visit_enum(PyEnumAccess::new(&value, variant))
+            // the mapping is being visited until the variant has been read
+            let container = self.enter()?;
+            let de = container.nested(Cow::Owned(value))?;
+            visitor.visit_enum(PyEnumAccess::new(de, variant, variants))
+        } else if let Some(tag) = &self.state.class_tag {
+            if self.visiting.is_some() {
+                // the payload of a newtype variant tagged by class is the object itself, which
+                // cannot be read as a class-tagged enum again
+                return Err(PythonizeError::circular_reference());
+            }
+            let variant = self.class_variant(tag, variants)?;
+            self.check_str_length(variant.len()?)?;
+            // the object holds the variant's contents, so is visited as a container
+            let de = self.enter()?;
+            visitor.visit_enum(PyEnumAccess::new(de, variant, variants))
         } else {
             Err(PythonizeError::invalid_enum_type())
         }
@@ -330,30 +1928,53 @@ impl<'de> de::Deserializer<'de> for &'_ mut Depythonizer<'_, '_> {
             .input
             .downcast::<PyString>()
             .map_err(|_| PythonizeError::dict_key_not_string())?;
-        visitor.visit_str(&s.to_cow()?)
+        self.check_type::<PyString>()?;
+        self.check_str_length(s.len()?)?;
+        if let Some(field) = self.field_name(s)? {
+            return visitor.visit_str(field);
+        }
+        self.visit_str(s, visitor)
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        self.ignore()?;
+        if self.state.unknown_keys != UnknownKeys::Warn {
+            self.lossy(|| format!("{} value ignored", type_name(&self.input.get_type())))?;
+        }
         visitor.visit_unit()
     }
 }
 
 struct PySequenceAccess<'a, 'py> {
-    seq: &'a Bound<'py, PySequence>,
+    seq: Bound<'py, PySequence>,
+    /// The items of a tuple borrowed for `'a`, which can then be borrowed from in turn
+    items: Option<&'a [Bound<'py, PyAny>]>,
     index: usize,
     len: usize,
+    de: Depythonizer<'a, 'py>,
 }
 
 impl<'a, 'py> PySequenceAccess<'a, 'py> {
-    fn new(seq: &'a Bound<'py, PySequence>, len: usize) -> Self {
-        Self { seq, index: 0, len }
+    fn new(
+        seq: Bound<'py, PySequence>,
+        items: Option<&'a [Bound<'py, PyAny>]>,
+        len: usize,
+        de: Depythonizer<'a, 'py>,
+    ) -> Self {
+        Self {
+            seq,
+            items,
+            index: 0,
+            len,
+            de,
+        }
     }
 }
 
-impl<'de> de::SeqAccess<'de> for PySequenceAccess<'_, '_> {
+impl<'de> de::SeqAccess<'de> for PySequenceAccess<'de, '_> {
     type Error = PythonizeError;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -361,9 +1982,15 @@ impl<'de> de::SeqAccess<'de> for PySequenceAccess<'_, '_> {
         T: de::DeserializeSeed<'de>,
     {
         if self.index < self.len {
-            let item = self.seq.get_item(self.index)?;
+            let index = self.index;
+            let item = match self.items {
+                Some(items) => Cow::Borrowed(&items[index]),
+                None => Cow::Owned(self.seq.get_item(index)?),
+            };
             self.index += 1;
-            seed.deserialize(&mut Depythonizer::from_object(&item))
+            let mut de = self.de.nested(item)?;
+            self.de
+                .in_path(PathEntry::Index(index), || seed.deserialize(&mut de))
                 .map(Some)
         } else {
             Ok(None)
@@ -371,50 +1998,125 @@ impl<'de> de::SeqAccess<'de> for PySequenceAccess<'_, '_> {
     }
 }
 
-struct PySetAsSequence<'py> {
+struct PyIterableAsSequence<'a, 'py> {
     iter: Bound<'py, PyIterator>,
+    /// Number of items left, if known or estimated
+    remaining: Option<usize>,
+    /// Number of items produced so far
+    count: usize,
+    de: Depythonizer<'a, 'py>,
 }
 
-impl<'py> PySetAsSequence<'py> {
-    fn from_set(set: &Bound<'py, PySet>) -> Self {
+impl<'a, 'py> PyIterableAsSequence<'a, 'py> {
+    fn new(
+        iter: Bound<'py, PyIterator>,
+        size_hint: Option<usize>,
+        de: Depythonizer<'a, 'py>,
+    ) -> Self {
         Self {
-            iter: PyIterator::from_object(set).expect("set is always iterable"),
+            iter,
+            remaining: size_hint,
+            count: 0,
+            de,
         }
     }
 
-    fn from_frozenset(set: &Bound<'py, PyFrozenSet>) -> Self {
-        Self {
-            iter: PyIterator::from_object(set).expect("frozenset is always iterable"),
+    /// The next item of the iterable, checking the length limit
+    fn next_item(&mut self) -> Result<Option<Bound<'py, PyAny>>> {
+        match self.iter.next() {
+            Some(item) => {
+                let item = item?;
+                // the length of an iterator is only known once it is exhausted
+                self.count += 1;
+                self.de.check_length(self.count)?;
+                self.remaining = self.remaining.map(|n| n.saturating_sub(1));
+                Ok(Some(item))
+            }
+            None => Ok(None),
         }
     }
 }
 
-impl<'de> de::SeqAccess<'de> for PySetAsSequence<'_> {
+impl<'de> de::SeqAccess<'de> for PyIterableAsSequence<'de, '_> {
     type Error = PythonizeError;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: de::DeserializeSeed<'de>,
     {
-        match self.iter.next() {
-            Some(item) => seed
-                .deserialize(&mut Depythonizer::from_object(&item?))
-                .map(Some),
+        match self.next_item()? {
+            Some(item) => {
+                let mut de = self.de.nested(Cow::Owned(item))?;
+                let index = PathEntry::Index(self.count - 1);
+                self.de
+                    .in_path(index, || seed.deserialize(&mut de))
+                    .map(Some)
+            }
             None => Ok(None),
         }
     }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining
+    }
+}
+
+struct PyPairsAsMapping<'a, 'py> {
+    inner: PyIterableAsSequence<'a, 'py>,
+    /// The pair whose key was deserialized last
+    pair: Option<(Bound<'py, PyAny>, Bound<'py, PyAny>)>,
+}
+
+impl<'de> de::MapAccess<'de> for PyPairsAsMapping<'de, '_> {
+    type Error = PythonizeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let pair = match self.inner.next_item()? {
+            Some(pair) => pair,
+            None => return Ok(None),
+        };
+        // don't read past a third item, the element may be a long or unbounded iterable
+        let items = pair.try_iter()?.take(3).collect::<PyResult<Vec<_>>>()?;
+        let [key, value] = <[_; 2]>::try_from(items)
+            .map_err(|items| PythonizeError::incorrect_sequence_length(2, items.len()))?;
+        let mut de = self.inner.de.nested(Cow::Owned(key.clone()))?;
+        self.pair = Some((key, value));
+        seed.deserialize(&mut de).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let (key, value) = self
+            .pair
+            .take()
+            .expect("next_value_seed should always be called after next_key_seed");
+        let mut de = self.inner.de.nested(Cow::Owned(value))?;
+        self.inner
+            .de
+            .in_path(PathEntry::Key(key), || seed.deserialize(&mut de))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.remaining
+    }
 }
 
-struct PyMappingAccess<'py> {
+struct PyMappingAccess<'a, 'py> {
     keys: Bound<'py, PyList>,
     values: Bound<'py, PyList>,
     key_idx: usize,
     val_idx: usize,
     len: usize,
+    de: Depythonizer<'a, 'py>,
 }
 
-impl<'py> PyMappingAccess<'py> {
-    fn new(map: &Bound<'py, PyMapping>) -> Result<Self> {
+impl<'a, 'py> PyMappingAccess<'a, 'py> {
+    fn new(map: &Bound<'py, PyMapping>, de: Depythonizer<'a, 'py>) -> Result<Self> {
         let keys = map.keys()?;
         let values = map.values()?;
         let len = map.len()?;
@@ -424,11 +2126,12 @@ impl<'py> PyMappingAccess<'py> {
             key_idx: 0,
             val_idx: 0,
             len,
+            de,
         })
     }
 }
 
-impl<'de> de::MapAccess<'de> for PyMappingAccess<'_> {
+impl<'de> de::MapAccess<'de> for PyMappingAccess<'de, '_> {
     type Error = PythonizeError;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
@@ -438,8 +2141,8 @@ impl<'de> de::MapAccess<'de> for PyMappingAccess<'_> {
         if self.key_idx < self.len {
             let item = self.keys.get_item(self.key_idx)?;
             self.key_idx += 1;
-            seed.deserialize(&mut Depythonizer::from_object(&item))
-                .map(Some)
+            let mut de = self.de.nested_key(Cow::Owned(item))?;
+            seed.deserialize(&mut de).map(Some)
         } else {
             Ok(None)
         }
@@ -449,27 +2152,177 @@ impl<'de> de::MapAccess<'de> for PyMappingAccess<'_> {
     where
         V: de::DeserializeSeed<'de>,
     {
+        let key = self.keys.get_item(self.val_idx)?;
         let item = self.values.get_item(self.val_idx)?;
         self.val_idx += 1;
-        seed.deserialize(&mut Depythonizer::from_object(&item))
+        let mut de = self.de.nested(Cow::Owned(item))?;
+        self.de
+            .in_path(PathEntry::Key(key), || seed.deserialize(&mut de))
+    }
+}
+
+struct PyAttributeAccess<'a, 'py> {
+    items: Vec<(Bound<'py, PyString>, Bound<'py, PyAny>)>,
+    key_idx: usize,
+    val_idx: usize,
+    de: Depythonizer<'a, 'py>,
+}
+
+impl<'a, 'py> PyAttributeAccess<'a, 'py> {
+    fn new(
+        items: Vec<(Bound<'py, PyString>, Bound<'py, PyAny>)>,
+        de: Depythonizer<'a, 'py>,
+    ) -> Self {
+        Self {
+            items,
+            key_idx: 0,
+            val_idx: 0,
+            de,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for PyAttributeAccess<'de, '_> {
+    type Error = PythonizeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.items.get(self.key_idx) {
+            Some((name, _)) => {
+                self.key_idx += 1;
+                let mut de = self.de.nested_key(Cow::Owned(name.clone().into_any()))?;
+                seed.deserialize(&mut de).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let (name, value) = &self.items[self.val_idx];
+        self.val_idx += 1;
+        let mut de = self.de.nested(Cow::Owned(value.clone()))?;
+        let key = PathEntry::Key(name.clone().into_any());
+        self.de.in_path(key, || seed.deserialize(&mut de))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len() - self.key_idx)
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for PyAttributeAccess<'de, '_> {
+    type Error = PythonizeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.items.get(self.val_idx) {
+            Some((_, value)) => {
+                let index = PathEntry::Index(self.val_idx);
+                self.val_idx += 1;
+                let mut de = self.de.nested(Cow::Owned(value.clone()))?;
+                self.de
+                    .in_path(index, || seed.deserialize(&mut de))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len() - self.val_idx)
+    }
+}
+
+/// A single object accessed as a sequence of one item
+struct PyScalarAsSequence<'a, 'py> {
+    de: Option<Depythonizer<'a, 'py>>,
+}
+
+impl<'de> de::SeqAccess<'de> for PyScalarAsSequence<'de, '_> {
+    type Error = PythonizeError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.de.take() {
+            Some(mut de) => seed.deserialize(&mut de).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(usize::from(self.de.is_some()))
+    }
+}
+
+/// Visits an empty string or bytes in place of the ones read, for targets which take any
+/// contents when only validating, see [`validate`]
+struct SkipContents<V>(V);
+
+impl<'de, V> de::Visitor<'de> for SkipContents<V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.expecting(formatter)
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> std::result::Result<V::Value, E> {
+        self.0.visit_borrowed_str("")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, _: &'de str) -> std::result::Result<V::Value, E> {
+        self.0.visit_borrowed_str("")
+    }
+
+    fn visit_string<E: de::Error>(self, _: String) -> std::result::Result<V::Value, E> {
+        self.0.visit_borrowed_str("")
+    }
+
+    fn visit_bytes<E: de::Error>(self, _: &[u8]) -> std::result::Result<V::Value, E> {
+        self.0.visit_borrowed_bytes(&[])
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, _: &'de [u8]) -> std::result::Result<V::Value, E> {
+        self.0.visit_borrowed_bytes(&[])
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, _: Vec<u8>) -> std::result::Result<V::Value, E> {
+        self.0.visit_borrowed_bytes(&[])
     }
 }
 
 struct PyEnumAccess<'a, 'py> {
     de: Depythonizer<'a, 'py>,
     variant: Bound<'py, PyString>,
+    variants: &'static [&'static str],
 }
 
 impl<'a, 'py> PyEnumAccess<'a, 'py> {
-    fn new(obj: &'a Bound<'py, PyAny>, variant: Bound<'py, PyString>) -> Self {
+    fn new(
+        de: Depythonizer<'a, 'py>,
+        variant: Bound<'py, PyString>,
+        variants: &'static [&'static str],
+    ) -> Self {
         Self {
-            de: Depythonizer::from_object(obj),
+            de,
             variant,
+            variants,
         }
     }
 }
 
-impl<'de> de::EnumAccess<'de> for PyEnumAccess<'_, '_> {
+impl<'de> de::EnumAccess<'de> for PyEnumAccess<'de, '_> {
     type Error = PythonizeError;
     type Variant = Self;
 
@@ -478,13 +2331,14 @@ impl<'de> de::EnumAccess<'de> for PyEnumAccess<'_, '_> {
         V: de::DeserializeSeed<'de>,
     {
         let cow = self.variant.to_cow()?;
-        let de: de::value::StrDeserializer<'_, PythonizeError> = cow.as_ref().into_deserializer();
+        let name = self.de.variant_name(&cow, self.variants);
+        let de: de::value::StrDeserializer<'_, PythonizeError> = name.into_deserializer();
         let val = seed.deserialize(de)?;
         Ok((val, self))
     }
 }
 
-impl<'de> de::VariantAccess<'de> for PyEnumAccess<'_, '_> {
+impl<'de> de::VariantAccess<'de> for PyEnumAccess<'de, '_> {
     type Error = PythonizeError;
 
     fn unit_variant(self) -> Result<()> {
@@ -502,26 +2356,40 @@ impl<'de> de::VariantAccess<'de> for PyEnumAccess<'_, '_> {
     where
         V: de::Visitor<'de>,
     {
+        // Objects tagged by class hold the fields as attributes, in order
+        if self.de.input.downcast::<PySequence>().is_err() {
+            if let Some(names) = self.de.attribute_names()? {
+                let attributes = self.de.attribute_access(names)?;
+                if attributes.items.len() != len {
+                    return Err(PythonizeError::incorrect_sequence_length(
+                        len,
+                        attributes.items.len(),
+                    ));
+                }
+                return visitor.visit_seq(attributes);
+            }
+        }
         visitor.visit_seq(self.de.sequence_access(Some(len))?)
     }
 
-    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_map(self.de.dict_access()?)
+        self.de.deserialize_struct_fields(fields, visitor)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::collections::BTreeMap;
     use std::ffi::CStr;
 
     use super::*;
-    use crate::error::ErrorImpl;
+    use crate::error::{ErrorImpl, ErrorKind, Limit};
     use maplit::hashmap;
     use pyo3::ffi::c_str;
-    use pyo3::{IntoPyObject, Python};
+    use pyo3::{IntoPyObject, PyErr, Python};
     use serde_json::{json, Value as JsonValue};
 
     fn test_de<T>(code: &CStr, expected: &T, expected_json: &JsonValue)
@@ -588,11 +2456,154 @@ mod test {
         Python::with_gil(|py| {
             let locals = PyDict::new(py);
             let obj = py.eval(code, None, Some(&locals)).unwrap();
+            let err = depythonize::<Struct>(&obj).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::MissingField("bar")));
+            assert_eq!(err.to_string(), "missing field `bar`");
+            assert!(PyErr::from(err).is_instance_of::<pyo3::exceptions::PyKeyError>(py));
+        })
+    }
+
+    #[test]
+    fn test_serde_errors() {
+        #[derive(Debug, Deserialize)]
+        enum Color {
+            Red,
+            Green,
+            Blue,
+        }
+
+        Python::with_gil(|py| {
+            let obj = py.eval(c_str!("'Purple'"), None, None).unwrap();
+            let err = depythonize::<Color>(&obj).unwrap_err();
             assert!(matches!(
-                *depythonize::<Struct>(&obj).unwrap_err().inner,
-                ErrorImpl::Message(msg) if msg == "missing field `bar`"
+                err.kind(),
+                ErrorKind::UnknownVariant { variant, expected: ["Red", "Green", "Blue"] }
+                    if variant == "Purple"
             ));
-        })
+            assert_eq!(
+                err.to_string(),
+                "unknown variant `Purple`, expected one of `Red`, `Green`, `Blue`"
+            );
+            assert!(PyErr::from(err).is_instance_of::<pyo3::exceptions::PyValueError>(py));
+
+            let obj = py.eval(c_str!("0"), None, None).unwrap();
+            let err = depythonize::<std::num::NonZeroU8>(&obj).unwrap_err();
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::InvalidValue { unexpected, .. } if unexpected == "integer `0`"
+            ));
+
+            let obj = py.eval(c_str!("'Bleu'"), None, None).unwrap();
+            let err = depythonize::<Color>(&obj).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "unknown variant `Bleu`, expected one of `Red`, `Green`, `Blue` \
+                    (did you mean `Blue`?)"
+            );
+        });
+    }
+
+    #[test]
+    fn test_error_context() {
+        #[derive(Debug, Deserialize)]
+        enum Color {
+            Red,
+            Green,
+        }
+
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(c_str!("{'Red': 1, 'Green': 2}"), None, None)
+                .unwrap();
+            let err = depythonize::<Color>(&obj).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "expected tagged enum dict to have exactly 1 key \
+                    (got {'Green': 2, 'Red': 1}, expected enum Color)"
+            );
+
+            let obj = py.eval(c_str!("list(range(100))"), None, None).unwrap();
+            let err = depythonize::<String>(&obj).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "unexpected type: 'list' object cannot be converted to 'PyString' \
+                    (got [0, 1, 2, 3, 4, 5, ...], expected a string)"
+            );
+
+            // errors from the target type already say what was expected
+            let obj = py.eval(c_str!("'x'"), None, None).unwrap();
+            let err = depythonize::<Color>(&obj).unwrap_err();
+            assert!(err.context.is_none());
+        });
+    }
+
+    #[test]
+    fn test_struct_from_sequence() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Point {
+            x: f64,
+            y: f64,
+            #[serde(default)]
+            z: f64,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Shape {
+            Circle { center: Point, radius: f64 },
+        }
+
+        Python::with_gil(|py| {
+            let check = |code: &CStr, expected: Point| {
+                let obj = py.eval(code, None, None).unwrap();
+                assert_eq!(depythonize::<Point>(&obj).unwrap(), expected);
+            };
+            check(
+                c_str!("(1.0, 2.0, 3.0)"),
+                Point {
+                    x: 1.0,
+                    y: 2.0,
+                    z: 3.0,
+                },
+            );
+            check(
+                c_str!("[1.0, 2.0]"),
+                Point {
+                    x: 1.0,
+                    y: 2.0,
+                    z: 0.0,
+                },
+            );
+
+            let obj = py
+                .eval(c_str!("{'Circle': [(1.0, 2.0), 3.0]}"), None, None)
+                .unwrap();
+            assert_eq!(
+                depythonize::<Shape>(&obj).unwrap(),
+                Shape::Circle {
+                    center: Point {
+                        x: 1.0,
+                        y: 2.0,
+                        z: 0.0
+                    },
+                    radius: 3.0
+                }
+            );
+
+            let obj = py.eval(c_str!("(1.0, 2.0, 3.0, 4.0)"), None, None).unwrap();
+            assert!(matches!(
+                *depythonize::<Point>(&obj).unwrap_err().inner,
+                ErrorImpl::IncorrectSequenceLength {
+                    expected: 3,
+                    got: 4
+                }
+            ));
+
+            let obj = py.eval(c_str!("(1.0,)"), None, None).unwrap();
+            assert_eq!(
+                depythonize::<Point>(&obj).unwrap_err().to_string(),
+                "missing field `y`"
+            );
+        });
     }
 
     #[test]
@@ -666,6 +2677,82 @@ mod test {
         test_de(code, &expected, &expected_json);
     }
 
+    #[test]
+    fn test_vec_from_iterable() {
+        fn check(code: &CStr) {
+            // iterators can only be consumed once, so evaluate the code for each conversion
+            Python::with_gil(|py| {
+                let obj = py.eval(code, None, None).unwrap();
+                assert_eq!(depythonize::<Vec<i32>>(&obj).unwrap(), vec![1, 2, 3]);
+                let obj = py.eval(code, None, None).unwrap();
+                assert_eq!(depythonize::<JsonValue>(&obj).unwrap(), json!([1, 2, 3]));
+            });
+        }
+
+        check(c_str!("(x for x in [1, 2, 3])"));
+        check(c_str!("{1: 'a', 2: 'b', 3: 'c'}.keys()"));
+        check(c_str!("__import__('itertools').chain([1], [2, 3])"));
+
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "from collections.abc import Set\n\
+                     class Numbers(Set):\n\
+                     \x20   def __init__(self, n): self.n = n\n\
+                     \x20   def __contains__(self, x): return x in range(self.n)\n\
+                     \x20   def __iter__(self): return iter(range(self.n))\n\
+                     \x20   def __len__(self): return self.n\n\
+                     numbers = Numbers(3)"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let obj = locals.get_item("numbers").unwrap().unwrap();
+            assert_eq!(depythonize::<Vec<i32>>(&obj).unwrap(), vec![0, 1, 2]);
+            assert_eq!(depythonize::<JsonValue>(&obj).unwrap(), json!([0, 1, 2]));
+
+            // mappings are not iterated as sequences of their keys
+            let obj = py.eval(c_str!("{1: 'a'}"), None, None).unwrap();
+            assert!(depythonize::<Vec<i32>>(&obj).is_err());
+        });
+    }
+
+    #[test]
+    fn test_iterable_size_hint() {
+        struct SizeHint;
+
+        impl<'de> de::Visitor<'de> for SizeHint {
+            type Value = Option<usize>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a sequence")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> std::result::Result<Option<usize>, A::Error> {
+                Ok(seq.size_hint())
+            }
+        }
+
+        Python::with_gil(|py| {
+            let check = |code: &CStr, expected: Option<usize>| {
+                let obj = py.eval(code, None, None).unwrap();
+                let mut de = Depythonizer::from_object(&obj);
+                assert_eq!(
+                    de::Deserializer::deserialize_seq(&mut de, SizeHint).unwrap(),
+                    expected
+                );
+            };
+            check(c_str!("{1, 2}"), Some(2));
+            check(c_str!("iter([1, 2, 3])"), Some(3));
+            check(c_str!("(x for x in [1, 2, 3])"), None);
+        });
+    }
+
     #[test]
     fn test_vec() {
         let expected = vec![3, 2, 1];
@@ -682,6 +2769,57 @@ mod test {
         test_de(code, &expected, &expected_json);
     }
 
+    #[test]
+    fn test_map_from_pairs() {
+        fn check(code: &CStr) {
+            Python::with_gil(|py| {
+                let obj = py.eval(code, None, None).unwrap();
+                assert_eq!(
+                    depythonize::<BTreeMap<String, i32>>(&obj).unwrap(),
+                    BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)])
+                );
+            });
+        }
+
+        check(c_str!("[('b', 2), ('a', 1)]"));
+        check(c_str!("(['b', 2], ['a', 1])"));
+        check(c_str!("((k, v) for k, v in [('b', 2), ('a', 1)])"));
+        check(c_str!("{'b': 2, 'a': 1}.items()"));
+
+        Python::with_gil(|py| {
+            let obj = py.eval(c_str!("[('a', 1, 2)]"), None, None).unwrap();
+            assert!(matches!(
+                *depythonize::<BTreeMap<String, i32>>(&obj)
+                    .unwrap_err()
+                    .inner,
+                ErrorImpl::IncorrectSequenceLength {
+                    expected: 2,
+                    got: 3
+                }
+            ));
+
+            // an unbounded element is rejected without being exhausted
+            let obj = py
+                .eval(c_str!("[__import__('itertools').count()]"), None, None)
+                .unwrap();
+            assert!(matches!(
+                *depythonize::<BTreeMap<i32, i32>>(&obj).unwrap_err().inner,
+                ErrorImpl::IncorrectSequenceLength {
+                    expected: 2,
+                    got: 3
+                }
+            ));
+        });
+    }
+
+    #[test]
+    fn test_pairs_from_map() {
+        let expected = vec![("b".to_string(), 2), ("a".to_string(), 1)];
+        let expected_json = json!({"b": 2, "a": 1});
+        let code = c_str!("{'b': 2, 'a': 1}");
+        test_de(code, &expected, &expected_json);
+    }
+
     #[test]
     fn test_hashmap() {
         let expected = hashmap! {"foo".to_string() => 4};
@@ -864,6 +3002,91 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_deserialize_bytes_from_buffers() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
+
+        Python::with_gil(|py| {
+            for code in [
+                c_str!("{'data': b'hello'}"),
+                c_str!("{'data': bytearray(b'hello')}"),
+                c_str!("{'data': memoryview(b'__hello__')[2:-2]}"),
+                c_str!("{'data': __import__('array').array('B', b'hello')}"),
+            ] {
+                let obj = py.eval(code, None, None).unwrap();
+                assert_eq!(
+                    depythonize::<Data>(&obj).unwrap(),
+                    Data {
+                        data: b"hello".to_vec()
+                    }
+                );
+            }
+
+            let obj = py.eval(c_str!("{'data': 'hello'}"), None, None).unwrap();
+            assert!(depythonize::<Data>(&obj).is_err());
+
+            // `&[u8]` borrows from `bytes` only, other buffers are copied
+            let obj = py.eval(c_str!("bytearray(b'hello')"), None, None).unwrap();
+            assert!(depythonize::<&[u8]>(&obj).is_err());
+        });
+    }
+
+    #[test]
+    fn test_vec_from_typed_buffer() {
+        Python::with_gil(|py| {
+            // `bytes` are a buffer of `u8`, read as a sequence of ints without going through
+            // `deserialize_bytes`
+            let obj = py.eval(c_str!("b'hi\\xff'"), None, None).unwrap();
+            assert_eq!(
+                depythonize::<Vec<u8>>(&obj).unwrap(),
+                vec![b'h', b'i', 0xff]
+            );
+            assert_eq!(depythonize::<Vec<u32>>(&obj).unwrap(), vec![104, 105, 255]);
+            assert!(depythonize::<Vec<i8>>(&obj).is_err());
+
+            let obj = py
+                .eval(
+                    c_str!("__import__('array').array('d', [1.5, -2.0])"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(depythonize::<Vec<f64>>(&obj).unwrap(), vec![1.5, -2.0]);
+            assert_eq!(depythonize::<JsonValue>(&obj).unwrap(), json!([1.5, -2.0]));
+
+            let obj = py
+                .eval(
+                    c_str!("memoryview(__import__('array').array('h', [1, -2, 3]))"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(depythonize::<Vec<i16>>(&obj).unwrap(), vec![1, -2, 3]);
+            assert_eq!(depythonize::<Vec<i64>>(&obj).unwrap(), vec![1, -2, 3]);
+
+            // items are still checked against the target type
+            let obj = py
+                .eval(
+                    c_str!("__import__('array').array('i', [1, 300])"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert!(depythonize::<Vec<u8>>(&obj).is_err());
+
+            // and against the limits
+            let mut de = Depythonizer::from_object(&obj).limits(Limits {
+                max_length: Some(1),
+                ..Limits::default()
+            });
+            assert!(Vec::<i32>::deserialize(&mut de).is_err());
+        });
+    }
+
     #[test]
     fn test_char() {
         let expected = 'a';
@@ -873,20 +3096,974 @@ mod test {
     }
 
     #[test]
-    fn test_unknown_type() {
+    fn test_circular_reference() {
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(c_str!("a = []; a.append(a)"), None, Some(&locals))
+                .unwrap();
+            let obj = locals.get_item("a").unwrap().unwrap();
+            let err = depythonize::<JsonValue>(&obj).unwrap_err();
+            assert!(matches!(*err.inner, ErrorImpl::CircularReference));
+
+            // the same object may appear more than once, as long as it doesn't contain itself
+            let obj = py
+                .eval(c_str!("(lambda a: [a, a])([1])"), None, None)
+                .unwrap();
+            assert_eq!(depythonize::<JsonValue>(&obj).unwrap(), json!([[1], [1]]));
+        });
+    }
+
+    #[test]
+    fn test_enum_recursion() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        enum Node {
+            Next(Box<Node>),
+            End,
+        }
+
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "cycle = {}; cycle['Next'] = cycle\n\
+                     deep = 'End'\n\
+                     for _ in range(100000): deep = {'Next': deep}\n\
+                     class Next: pass\n\
+                     tagged = Next()"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+
+            let cycle = locals.get_item("cycle").unwrap().unwrap();
+            let err = depythonize::<Node>(&cycle).unwrap_err();
+            assert!(matches!(*err.inner, ErrorImpl::CircularReference));
+
+            let deep = locals.get_item("deep").unwrap().unwrap();
+            let err = depythonize::<Node>(&deep).unwrap_err();
+            assert!(matches!(*err.inner, ErrorImpl::DepthLimitExceeded(128)));
+
+            // an object tagged by class would otherwise be its own payload forever
+            let tagged = locals.get_item("tagged").unwrap().unwrap();
+            let mut de = Depythonizer::from_object(&tagged).class_tag(ClassTag::ClassName);
+            let err = Node::deserialize(&mut de).unwrap_err();
+            assert!(matches!(*err.inner, ErrorImpl::CircularReference));
+        });
+    }
+
+    #[test]
+    fn test_max_depth() {
+        Python::with_gil(|py| {
+            let obj = py.eval(c_str!("[{'a': [1]}]"), None, None).unwrap();
+
+            let mut de = Depythonizer::from_object(&obj).max_depth(2);
+            let err = JsonValue::deserialize(&mut de).unwrap_err();
+            assert!(matches!(*err.inner, ErrorImpl::DepthLimitExceeded(2)));
+
+            let mut de = Depythonizer::from_object(&obj).max_depth(3);
+            assert_eq!(
+                JsonValue::deserialize(&mut de).unwrap(),
+                json!([{"a": [1]}])
+            );
+        });
+    }
+
+    #[test]
+    fn test_limits() {
+        fn check_limit(code: &CStr, limits: Limits, expected: Limit) {
+            Python::with_gil(|py| {
+                let obj = py.eval(code, None, None).unwrap();
+
+                let mut de = Depythonizer::from_object(&obj).limits(limits);
+                let err = JsonValue::deserialize(&mut de).unwrap_err();
+                assert!(matches!(
+                    *err.inner,
+                    ErrorImpl::LimitExceeded { limit, max: 2, got: 3 } if limit == expected
+                ));
+
+                // The default limits accept the same input
+                assert!(depythonize::<JsonValue>(&obj).is_ok());
+            });
+        }
+
+        check_limit(
+            c_str!("[1, [2]]"),
+            Limits {
+                max_nodes: Some(2),
+                ..Limits::default()
+            },
+            Limit::Nodes,
+        );
+        check_limit(
+            c_str!("{'a': 1, 'b': 2, 'c': 3}"),
+            Limits {
+                max_length: Some(2),
+                ..Limits::default()
+            },
+            Limit::Length,
+        );
+        check_limit(
+            c_str!("['abc']"),
+            Limits {
+                max_str_length: Some(2),
+                ..Limits::default()
+            },
+            Limit::StrLength,
+        );
+        check_limit(
+            c_str!("[4]"),
+            Limits {
+                max_int_bits: Some(2),
+                ..Limits::default()
+            },
+            Limit::IntBits,
+        );
+    }
+
+    #[test]
+    fn test_struct_from_attributes() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Point {
+            x: i32,
+            y: i32,
+            #[serde(default)]
+            label: Option<String>,
+        }
+
+        let expected = Point {
+            x: 1,
+            y: 2,
+            label: None,
+        };
+        let expected_json = json!({"x": 1, "y": 2});
+
+        // dataclass
+        test_de(
+            c_str!("__import__('dataclasses').make_dataclass('Point', ['x', 'y'])(1, 2)"),
+            &expected,
+            &expected_json,
+        );
+        // namedtuple
+        test_de(
+            c_str!("__import__('collections').namedtuple('Point', ['x', 'y'])(1, 2)"),
+            &expected,
+            &expected_json,
+        );
+        // plain object with a __dict__
+        test_de(
+            c_str!("__import__('types').SimpleNamespace(x=1, y=2)"),
+            &expected,
+            &expected_json,
+        );
+
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "class Base:\n    __slots__ = ('x',)\n\
+                     class Point(Base):\n    __slots__ = 'y'\n    is_point = True\n\
+                     p = Point(); p.x = 1; p.y = 2"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let obj = locals.get_item("p").unwrap().unwrap();
+            assert_eq!(depythonize::<Point>(&obj).unwrap(), expected);
+            assert_eq!(depythonize::<JsonValue>(&obj).unwrap(), expected_json);
+
+            // unset slots are skipped
+            py.run(c_str!("del p.y"), None, Some(&locals)).unwrap();
+            assert_eq!(depythonize::<JsonValue>(&obj).unwrap(), json!({"x": 1}));
+            let err = depythonize::<Point>(&obj).unwrap_err();
+            assert_eq!(err.to_string(), "missing field `y`");
+
+            // private attributes are skipped
+            py.run(
+                c_str!("q = __import__('types').SimpleNamespace(x=1, _cache=2)"),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let obj = locals.get_item("q").unwrap().unwrap();
+            assert_eq!(depythonize::<JsonValue>(&obj).unwrap(), json!({"x": 1}));
+
+            // objects without public attributes, exceptions and enum members are not records
+            for code in [
+                c_str!("__import__('pathlib').PurePosixPath('/a/b')"),
+                c_str!("ValueError('boom')"),
+                c_str!("__import__('uuid').SafeUUID.safe"),
+                c_str!("__import__('uuid').UUID(int=5)"),
+            ] {
+                let obj = py.eval(code, None, None).unwrap();
+                let err = depythonize::<JsonValue>(&obj).unwrap_err();
+                assert!(
+                    matches!(*err.inner, ErrorImpl::UnsupportedType(_)),
+                    "{}",
+                    err
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn test_struct_from_builtin_value() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Point {
+            x: i32,
+        }
+
+        Python::with_gil(|py| {
+            let obj = 5i32.into_pyobject(py).unwrap().into_any();
+            let err = depythonize::<Point>(&obj).unwrap_err();
+            assert!(matches!(*err.inner, ErrorImpl::UnexpectedType(_)));
+        });
+    }
+
+    #[test]
+    fn test_enum_from_class() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Shape {
+            Circle { radius: f64 },
+            Square { side: u32 },
+            Empty,
+        }
+
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "from dataclasses import dataclass\n\
+                     class Shape:\n    pass\n\
+                     @dataclass\nclass Circle(Shape):\n    radius: float\n    kind = 'Circle'\n\
+                     @dataclass\nclass Square(Shape):\n    side: int\n    kind = 'Square'\n\
+                     class BigSquare(Square):\n    pass\n\
+                     class Empty(Shape):\n    kind = 'Empty'\n\
+                     shapes = [Circle(1.5), Square(2), BigSquare(3), Empty(), 'Empty', {'Square': {'side': 4}}]"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let shapes = locals.get_item("shapes").unwrap().unwrap();
+            let expected = vec![
+                Shape::Circle { radius: 1.5 },
+                Shape::Square { side: 2 },
+                Shape::Square { side: 3 },
+                Shape::Empty,
+                Shape::Empty,
+                Shape::Square { side: 4 },
+            ];
+
+            let mut de = Depythonizer::from_object(&shapes).class_tag(ClassTag::ClassName);
+            assert_eq!(Vec::<Shape>::deserialize(&mut de).unwrap(), expected);
+
+            let mut de = Depythonizer::from_object(&shapes)
+                .class_tag(ClassTag::Attribute("kind".to_string()));
+            assert_eq!(Vec::<Shape>::deserialize(&mut de).unwrap(), expected);
+
+            // without a class tag, objects are not accepted as enums
+            let err = depythonize::<Vec<Shape>>(&shapes).unwrap_err();
+            assert!(matches!(*err.inner, ErrorImpl::InvalidEnumType));
+        });
+    }
+
+    #[test]
+    fn test_borrowed_str_and_bytes() {
+        Python::with_gil(|py| {
+            let obj = py.eval(c_str!("'hello'"), None, None).unwrap();
+            let s: &str = depythonize(&obj).unwrap();
+            assert_eq!(s, "hello");
+            let cached = obj.downcast::<PyString>().unwrap().to_str().unwrap();
+            assert_eq!(s.as_ptr(), cached.as_ptr());
+
+            let obj = py.eval(c_str!("b'world'"), None, None).unwrap();
+            let b: &[u8] = depythonize(&obj).unwrap();
+            assert_eq!(b, b"world");
+
+            // items of a tuple are borrowed too
+            let obj = py.eval(c_str!("('a', (b'b', 'c'))"), None, None).unwrap();
+            let t: (&str, (&[u8], &str)) = depythonize(&obj).unwrap();
+            assert_eq!(t, ("a", (&b"b"[..], "c")));
+
+            // but items of a list may be replaced, so cannot be borrowed
+            let obj = py.eval(c_str!("['a']"), None, None).unwrap();
+            assert!(depythonize::<Vec<&str>>(&obj).is_err());
+            assert_eq!(depythonize::<Vec<String>>(&obj).unwrap(), vec!["a"]);
+        });
+    }
+
+    #[test]
+    fn test_strictness() {
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "import collections, enum\n\
+                     class Color(enum.IntEnum):\n\
+                     \x20   RED = 1\n\
+                     class Name(str): pass"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let eval = |code: &CStr| py.eval(code, None, Some(&locals)).unwrap();
+            let check = |code: &CStr, strictness: Strictness| -> Result<JsonValue> {
+                let obj = eval(code);
+                let mut de = Depythonizer::from_object(&obj).strictness(strictness);
+                JsonValue::deserialize(&mut de)
+            };
+            fn strict<T: de::DeserializeOwned>(obj: &Bound<'_, PyAny>) -> Result<T> {
+                T::deserialize(&mut Depythonizer::from_object(obj).strictness(Strictness::Strict))
+            }
+            fn message(err: PythonizeError) -> String {
+                match *err.inner {
+                    ErrorImpl::UnexpectedType(msg) => msg,
+                    other => panic!("unexpected error {:?}", other),
+                }
+            }
+
+            // lax by default
+            assert!(depythonize::<bool>(&eval(c_str!("'false'"))).unwrap());
+            assert_eq!(depythonize::<i64>(&eval(c_str!("True"))).unwrap(), 1);
+            assert_eq!(depythonize::<f64>(&eval(c_str!("1"))).unwrap(), 1.0);
+
+            let err = strict::<bool>(&eval(c_str!("'false'"))).unwrap_err();
+            assert_eq!(message(err), "expected bool, got str");
+            let err = strict::<bool>(&eval(c_str!("1"))).unwrap_err();
+            assert_eq!(message(err), "expected bool, got int");
+            let err = strict::<i64>(&eval(c_str!("True"))).unwrap_err();
+            assert_eq!(message(err), "expected int, got bool");
+            let err = strict::<f64>(&eval(c_str!("1"))).unwrap_err();
+            assert_eq!(message(err), "expected float, got int");
+            assert!(strict::<i64>(&eval(c_str!("1.0"))).is_err());
+            assert!(!strict::<bool>(&eval(c_str!("False"))).unwrap());
+
+            // subclasses are accepted unless exact types are required
+            assert_eq!(
+                check(c_str!("True"), Strictness::Strict).unwrap(),
+                json!(true)
+            );
+            let subclasses = c_str!("[Color.RED, Name('a'), collections.OrderedDict(a=1)]");
+            assert_eq!(
+                check(subclasses, Strictness::Strict).unwrap(),
+                json!([1, "a", {"a": 1}])
+            );
+            let err = check(c_str!("Color.RED"), Strictness::Exact).unwrap_err();
+            assert_eq!(message(err), "expected int, got Color");
+            let err = check(c_str!("Name('a')"), Strictness::Exact).unwrap_err();
+            assert_eq!(message(err), "expected str, got Name");
+            let err = check(c_str!("collections.OrderedDict(a=1)"), Strictness::Exact);
+            assert_eq!(message(err.unwrap_err()), "expected dict, got OrderedDict");
+            assert_eq!(
+                check(c_str!("{'a': [1, 2.5, 'b', None]}"), Strictness::Exact).unwrap(),
+                json!({"a": [1, 2.5, "b", null]})
+            );
+        });
+    }
+
+    #[test]
+    fn test_coercions() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            port: u16,
+            ratio: f64,
+            debug: bool,
+            name: String,
+            tags: Vec<String>,
+        }
+
+        Python::with_gil(|py| {
+            fn coerce<T: de::DeserializeOwned>(
+                obj: &Bound<'_, PyAny>,
+                coercions: Coercions,
+            ) -> Result<T> {
+                T::deserialize(&mut Depythonizer::from_object(obj).coercions(coercions))
+            }
+
+            let env = py
+                .eval(
+                    c_str!("{'port': '8080', 'ratio': '0.5', 'debug': 'TRUE', 'name': 42, 'tags': 'a'}"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert!(depythonize::<Config>(&env).is_err());
+            assert_eq!(
+                coerce::<Config>(&env, Coercions::all()).unwrap(),
+                Config {
+                    port: 8080,
+                    ratio: 0.5,
+                    debug: true,
+                    name: "42".to_string(),
+                    tags: vec!["a".to_string()],
+                }
+            );
+
+            // each coercion is enabled separately
+            let str_to_number = Coercions {
+                str_to_number: true,
+                ..Coercions::default()
+            };
+            let obj = py.eval(c_str!("'12'"), None, None).unwrap();
+            assert_eq!(coerce::<i64>(&obj, str_to_number).unwrap(), 12);
+            // without `scalar_to_seq`, a str is a sequence of characters
+            assert_eq!(
+                coerce::<Vec<String>>(&obj, str_to_number).unwrap(),
+                vec!["1", "2"]
+            );
+            assert!(coerce::<i64>(&obj, Coercions::default()).is_err());
+
+            let obj = py.eval(c_str!("'yes'"), None, None).unwrap();
+            assert!(depythonize::<bool>(&obj).unwrap());
+            assert!(coerce::<bool>(&obj, Coercions::all()).is_err());
+
+            let integral_float_to_int = Coercions {
+                integral_float_to_int: true,
+                ..Coercions::default()
+            };
+            let obj = py.eval(c_str!("3.0"), None, None).unwrap();
+            assert_eq!(coerce::<u8>(&obj, integral_float_to_int).unwrap(), 3);
+            let obj = py.eval(c_str!("3.5"), None, None).unwrap();
+            assert!(coerce::<u8>(&obj, integral_float_to_int).is_err());
+
+            let bytes_to_str = Coercions {
+                bytes_to_str: true,
+                ..Coercions::default()
+            };
+            let obj = py.eval(c_str!("'é'.encode()"), None, None).unwrap();
+            assert_eq!(coerce::<String>(&obj, bytes_to_str).unwrap(), "é");
+            let obj = py.eval(c_str!("b'\\xff'"), None, None).unwrap();
+            assert!(coerce::<String>(&obj, bytes_to_str).is_err());
+
+            // the string length limit applies before parsing a number
+            let obj = py.eval(c_str!("'123'"), None, None).unwrap();
+            let limited = || {
+                Depythonizer::from_object(&obj)
+                    .coercions(str_to_number)
+                    .limits(Limits {
+                        max_str_length: Some(2),
+                        ..Limits::default()
+                    })
+            };
+            for result in [
+                i64::deserialize(&mut limited()).map(drop),
+                f64::deserialize(&mut limited()).map(drop),
+            ] {
+                assert!(matches!(
+                    *result.unwrap_err().inner,
+                    ErrorImpl::LimitExceeded {
+                        limit: Limit::StrLength,
+                        max: 2,
+                        got: 3
+                    }
+                ));
+            }
+        });
+    }
+
+    #[test]
+    fn test_rename() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Event {
+            UserCreated { user_id: u32 },
+            ServerStopped,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Envelope {
+            event_log: Vec<Event>,
+        }
+
+        let envelope = Envelope {
+            event_log: vec![Event::UserCreated { user_id: 1 }, Event::ServerStopped],
+        };
+
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(
+                    c_str!("{'eventLog': [{'user_created': {'userId': 1}}, 'server_stopped']}"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let mut de = Depythonizer::from_object(&obj)
+                .rename_fields(RenameRule::CamelCase)
+                .rename_variants(RenameRule::SnakeCase);
+            assert_eq!(Envelope::deserialize(&mut de).unwrap(), envelope);
+            assert!(depythonize::<Envelope>(&obj).is_err());
+
+            // names in Rust are still accepted, and case is ignored if enabled
+            let obj = py
+                .eval(
+                    c_str!("{'EVENTLOG': [{'UserCreated': {'userid': 1}}, 'Server_Stopped']}"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let mut de = Depythonizer::from_object(&obj)
+                .rename_fields(RenameRule::CamelCase)
+                .rename_variants(RenameRule::SnakeCase)
+                .case_insensitive(true);
+            assert_eq!(Envelope::deserialize(&mut de).unwrap(), envelope);
+        });
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Server {
+            port: u16,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Config {
+            servers: Vec<Server>,
+        }
+
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(
+                    c_str!("{'servers': [{'port': 80}, {'port': 81, 'prot': 'tcp'}], 'debug': {'a': 1}}"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let expected = Config {
+                servers: vec![Server { port: 80 }, Server { port: 81 }],
+            };
+            let key = |k: &str| PathSegment::Key(k.to_string());
+
+            let (config, ignored) = depythonize_with_ignored::<Config>(&obj).unwrap();
+            assert_eq!(config, expected);
+            assert_eq!(
+                ignored,
+                vec![
+                    Path::new(vec![key("servers"), PathSegment::Index(1), key("prot")]),
+                    Path::new(vec![key("debug")]),
+                ]
+            );
+            assert_eq!(ignored[0].to_string(), "servers[1].prot");
+
+            let mut de = Depythonizer::from_object(&obj).unknown_keys(UnknownKeys::Error);
+            let err = Config::deserialize(&mut de).unwrap_err();
+            assert_eq!(err.to_string(), "unknown key servers[1].prot");
+
+            let warnings = py.import("warnings").unwrap();
+            let caught = warnings
+                .call_method(
+                    "catch_warnings",
+                    (),
+                    Some(&[("record", true)].into_py_dict(py).unwrap()),
+                )
+                .unwrap();
+            let log = caught.call_method0("__enter__").unwrap();
+            warnings.call_method1("simplefilter", ("always",)).unwrap();
+            let mut de = Depythonizer::from_object(&obj).unknown_keys(UnknownKeys::Warn);
+            assert_eq!(Config::deserialize(&mut de).unwrap(), expected);
+            caught
+                .call_method1("__exit__", (py.None(), py.None(), py.None()))
+                .unwrap();
+            assert_eq!(log.len().unwrap(), 2);
+            let message = log.get_item(1).unwrap().getattr("message").unwrap();
+            assert_eq!(
+                message.str().unwrap().to_cow().unwrap(),
+                "unknown key debug"
+            );
+            assert_eq!(de.ignored_keys().len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_warn_lossy() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            ratio: f32,
+            flag: bool,
+            tags: Vec<String>,
+        }
+
+        Python::with_gil(|py| {
+            let warnings = py.import("warnings").unwrap();
+            let record = |code: &CStr, warn: bool| {
+                let obj = py.eval(code, None, None).unwrap();
+                let caught = warnings
+                    .call_method(
+                        "catch_warnings",
+                        (),
+                        Some(&[("record", true)].into_py_dict(py).unwrap()),
+                    )
+                    .unwrap();
+                let log = caught.call_method0("__enter__").unwrap();
+                warnings.call_method1("simplefilter", ("always",)).unwrap();
+                let mut de = Depythonizer::from_object(&obj).warn_lossy(warn);
+                let result = Data::deserialize(&mut de);
+                caught
+                    .call_method1("__exit__", (py.None(), py.None(), py.None()))
+                    .unwrap();
+                (
+                    result.unwrap(),
+                    log.extract::<Vec<Bound<'_, PyAny>>>().unwrap(),
+                )
+            };
+
+            let lossy = c_str!("{'ratio': 0.1, 'flag': 1, 'tags': {'a'}, 'extra': [1]}");
+            let (data, log) = record(lossy, true);
+            assert_eq!(data.tags, ["a"]);
+            let category = ExceptionClass::PythonizeWarning.type_object(py).unwrap();
+            let messages: Vec<String> = log
+                .iter()
+                .map(|w| {
+                    assert!(w.getattr("category").unwrap().is(&category));
+                    w.getattr("message").unwrap().str().unwrap().to_string()
+                })
+                .collect();
+            assert_eq!(
+                messages,
+                [
+                    "float 0.1 rounded to 0.10000000149011612 as f32 at ratio",
+                    "int converted to bool by truthiness at flag",
+                    "set read as a sequence in arbitrary order at tags",
+                    "list value ignored at extra",
+                ]
+            );
+            let path = log[0].getattr("message").unwrap().getattr("path").unwrap();
+            assert_eq!(path.repr().unwrap().to_string(), "('ratio',)");
+
+            assert!(record(lossy, false).1.is_empty());
+            let exact = c_str!("{'ratio': 0.5, 'flag': True, 'tags': ['a']}");
+            assert!(record(exact, true).1.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_fallback() {
         Python::with_gil(|py| {
             let obj = py
-                .import("decimal")
+                .eval(
+                    c_str!(
+                        "{'when': __import__('datetime').date(2024, 1, 2), \
+                            'where': __import__('pathlib').PurePosixPath('/tmp')}"
+                    ),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let err = depythonize::<JsonValue>(&obj).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::UnsupportedType(_)));
+
+            let path_class = py
+                .import("pathlib")
+                .unwrap()
+                .getattr("PurePosixPath")
+                .unwrap()
+                .downcast_into::<PyType>()
+                .unwrap();
+            let mut de = Depythonizer::from_object(&obj)
+                .fallback(|obj| obj.call_method0("isoformat"))
+                .fallback_for(path_class.clone(), |obj| Ok(obj.str()?.into_any()));
+            assert_eq!(
+                JsonValue::deserialize(&mut de).unwrap(),
+                json!({"when": "2024-01-02", "where": "/tmp"})
+            );
+
+            // class fallbacks also apply to typed targets
+            #[derive(Debug, Deserialize, PartialEq)]
+            struct Job {
+                r#where: String,
+            }
+            let mut de = Depythonizer::from_object(&obj)
+                .fallback_for(path_class, |obj| Ok(obj.str()?.into_any()));
+            assert_eq!(
+                Job::deserialize(&mut de).unwrap(),
+                Job {
+                    r#where: "/tmp".to_string()
+                }
+            );
+
+            // unsupported objects inside a substitute are passed to the fallback too
+            let default = py
+                .eval(
+                    c_str!("lambda obj: {'date': obj} if hasattr(obj, 'year') else str(obj)"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let obj = py
+                .eval(
+                    c_str!("[__import__('datetime').date(2024, 1, 2)]"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let mut de = Depythonizer::from_object(&obj).fallback_callable(default);
+            let err = JsonValue::deserialize(&mut de).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::DepthLimitExceeded(_)));
+
+            let default = py
+                .eval(c_str!("lambda obj: {'year': obj.year}"), None, None)
+                .unwrap();
+            let mut de = Depythonizer::from_object(&obj).fallback_callable(default);
+            assert_eq!(
+                JsonValue::deserialize(&mut de).unwrap(),
+                json!([{"year": 2024}])
+            );
+
+            // but not to the substitute itself
+            let mut de = Depythonizer::from_object(&obj).fallback(|obj| Ok(obj.clone()));
+            let err = JsonValue::deserialize(&mut de).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::UnsupportedType(_)));
+
+            // instances of plain classes are passed to the fallbacks rather than read as maps
+            let locals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "class Money:\n\
+                     \x20   def __init__(self): self.cents = 150; self.currency = 'EUR'\n\
+                     obj = [Money()]"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let obj = locals.get_item("obj").unwrap().unwrap();
+            assert_eq!(
+                depythonize::<JsonValue>(&obj).unwrap(),
+                json!([{"cents": 150, "currency": "EUR"}])
+            );
+            let mut de = Depythonizer::from_object(&obj)
+                .fallback(|obj| Ok(obj.getattr("cents")?.str()?.into_any()));
+            assert_eq!(JsonValue::deserialize(&mut de).unwrap(), json!(["150"]));
+            let money_class = locals
+                .get_item("Money")
                 .unwrap()
-                .getattr("Decimal")
                 .unwrap()
-                .call0()
+                .downcast_into::<PyType>()
+                .unwrap();
+            let mut de = Depythonizer::from_object(&obj)
+                .fallback(|obj| Ok(obj.getattr("cents")?.str()?.into_any()))
+                .fallback_for(money_class, |obj| obj.getattr("cents"));
+            assert_eq!(JsonValue::deserialize(&mut de).unwrap(), json!([150]));
+        });
+    }
+
+    #[test]
+    fn test_collect_errors() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            name: String,
+            servers: Vec<Server>,
+            retries: Option<u8>,
+        }
+
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(
+                    c_str!(
+                        "{'name': 1, 'retries': 300, \
+                            'servers': [{'host': 'a', 'port': 'x'}, {'host': 2}]}"
+                    ),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let collect =
+                |obj| Config::deserialize(&mut Depythonizer::from_object(obj).collect_errors(true));
+
+            let err = collect(&obj).unwrap_err();
+            let paths: Vec<String> = err.errors().iter().map(|e| e.path().to_string()).collect();
+            assert_eq!(
+                paths,
+                vec![
+                    "name",
+                    "retries",
+                    "servers[0].port",
+                    "servers[1].host",
+                    "servers[1]"
+                ]
+            );
+            assert!(err.to_string().starts_with(
+                "5 validation errors\nname\n  unexpected type: 'int' object cannot be converted to 'PyString' (got 1, expected a string)\nretries\n"
+            ));
+            assert!(err
+                .to_string()
+                .ends_with("\nservers[1]\n  missing field `port`"));
+
+            // without collecting, the first error is returned
+            let err = depythonize::<Config>(&obj).unwrap_err();
+            assert_eq!(err.path().to_string(), "name");
+            assert_eq!(err.errors().len(), 1);
+
+            let obj = py
+                .eval(
+                    c_str!("{'name': 'a', 'servers': [], 'retries': None}"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert!(collect(&obj).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_validate() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Server {
+            host: String,
+            port: u16,
+        }
+
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(c_str!("[{'host': 'a', 'port': 80}]"), None, None)
+                .unwrap();
+            assert!(validate::<Vec<Server>>(&obj).is_ok());
+
+            let obj = py
+                .eval(
+                    c_str!("[{'host': 'a', 'port': 80}, {'host': 1, 'port': -1}]"),
+                    None,
+                    None,
+                )
                 .unwrap();
+            let err = validate::<Vec<Server>>(&obj).unwrap_err();
+            assert_eq!(err.errors().len(), 2);
+
+            let err = PyErr::from(err);
+            assert!(err.is_instance_of::<pyo3::exceptions::PyValueError>(py));
+            let errors = err.value(py).getattr("errors").unwrap();
+            let locs: Vec<String> = errors
+                .try_iter()
+                .unwrap()
+                .map(|e| {
+                    e.unwrap()
+                        .get_item("loc")
+                        .unwrap()
+                        .repr()
+                        .unwrap()
+                        .to_string()
+                })
+                .collect();
+            assert_eq!(locs, vec!["(1, 'host')", "(1, 'port')"]);
+
+            // owned strings are checked without their contents being visited
+            thread_local! {
+                static VISITED: Cell<usize> = const { Cell::new(0) };
+            }
+            struct Text;
+            impl<'de> Deserialize<'de> for Text {
+                fn deserialize<D: de::Deserializer<'de>>(
+                    deserializer: D,
+                ) -> std::result::Result<Self, D::Error> {
+                    let s = String::deserialize(deserializer)?;
+                    VISITED.with(|visited| visited.set(visited.get() + s.len()));
+                    Ok(Text)
+                }
+            }
+            let obj = py.eval(c_str!("['hello', 'world']"), None, None).unwrap();
+            assert!(validate::<Vec<Text>>(&obj).is_ok());
+            assert_eq!(VISITED.with(Cell::get), 0);
+            depythonize::<Vec<Text>>(&obj).unwrap();
+            assert_eq!(VISITED.with(Cell::get), 10);
+
+            let obj = py.eval(c_str!("['hello', 1]"), None, None).unwrap();
+            let err = validate::<Vec<Text>>(&obj).unwrap_err();
+            assert_eq!(err.errors()[0].path().to_string(), "[1]");
+        });
+    }
+
+    #[test]
+    fn test_unsupported_object() {
+        Python::with_gil(|py| {
+            let obj = py.eval(c_str!("object()"), None, None).unwrap();
+            let err = depythonize::<JsonValue>(&obj).unwrap_err();
+            assert!(matches!(
+                *err.inner,
+                ErrorImpl::UnsupportedType(name) if name == "object"
+            ));
+        });
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        Python::with_gil(|py| {
+            let obj = py.eval(c_str!("complex(1, 2)"), None, None).unwrap();
             let err = depythonize::<serde_json::Value>(&obj).unwrap_err();
             assert!(matches!(
                 *err.inner,
-                ErrorImpl::UnsupportedType(name) if name == "Decimal"
+                ErrorImpl::UnsupportedType(name) if name == "complex"
+            ));
+        });
+    }
+
+    #[test]
+    fn test_numeric_protocol() {
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "import decimal\n\
+                     class Index:\n\
+                     \x20   def __init__(self, v): self.v = v\n\
+                     \x20   def __index__(self): return self.v\n\
+                     class Float:\n\
+                     \x20   def __init__(self, v): self.v = v\n\
+                     \x20   def __float__(self): return self.v\n\
+                     class Array:\n\
+                     \x20   def __iter__(self): return iter([1, 2, 3])\n\
+                     \x20   def __index__(self): raise TypeError('not a scalar')\n\
+                     \x20   def __float__(self): return 1.0\n\
+                     class Vector:\n\
+                     \x20   def __iter__(self): return iter([1.5, 2.5])\n\
+                     \x20   def __float__(self): return 4.0"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let eval = |code: &CStr| py.eval(code, None, Some(&locals)).unwrap();
+
+            let obj = eval(c_str!(
+                "[Index(1), Index(-2**40), Float(1.5), decimal.Decimal('2.5')]"
+            ));
+            assert_eq!(
+                depythonize::<JsonValue>(&obj).unwrap(),
+                json!([1, -1099511627776i64, 1.5, 2.5])
+            );
+
+            let obj = eval(c_str!(
+                "(Index(1), Index(-5), Index(2**100), Float(2.5), Index(3))"
             ));
+            assert_eq!(
+                depythonize::<(u8, i64, u128, f64, f32)>(&obj).unwrap(),
+                (1, -5, 1 << 100, 2.5, 3.0)
+            );
+
+            // `__float__` alone doesn't make an object integer-like
+            assert!(depythonize::<i64>(&eval(c_str!("Float(1.0)"))).is_err());
+
+            // containers are read as sequences, even if `__index__` or `__float__` is implemented
+            assert_eq!(
+                depythonize::<JsonValue>(&eval(c_str!("Array()"))).unwrap(),
+                json!([1, 2, 3])
+            );
+            assert_eq!(
+                depythonize::<JsonValue>(&eval(c_str!("Vector()"))).unwrap(),
+                json!([1.5, 2.5])
+            );
         });
     }
 }
@@ -1,19 +1,39 @@
 use pyo3::PyErr;
-use pyo3::{exceptions::*, DowncastError, DowncastIntoError};
+use pyo3::{DowncastError, DowncastIntoError};
 use serde::{de, ser};
 use std::convert::Infallible;
 use std::error;
 use std::fmt::{self, Debug, Display};
 use std::result;
 
+use crate::exceptions;
+use crate::path::{Path, PathSegment};
+use crate::rename;
+
 /// Alias for `std::result::Result` with error type `PythonizeError`
 pub type Result<T> = result::Result<T, PythonizeError>;
 
 /// Errors that can occur when serializing/deserializing Python objects
 pub struct PythonizeError {
     pub(crate) inner: Box<ErrorImpl>,
+    /// The location of the value which caused the error, relative to the container the error
+    /// has propagated to. `None` for the root, so that errors stay small.
+    pub(crate) path: Option<Box<Path>>,
+    /// The offending input and what was expected of it, for errors which don't say
+    pub(crate) context: Option<Box<Context>>,
+}
+
+/// The input which caused an error, and what the target type expected instead
+pub(crate) struct Context {
+    /// A truncated `repr()` of the input
+    pub(crate) value: String,
+    /// The description of the visitor, such as "struct Config" or "a string"
+    pub(crate) expected: String,
 }
 
+/// The path of errors which occurred at the root of the input
+static ROOT: Path = Path::new(Vec::new());
+
 impl PythonizeError {
     pub(crate) fn msg<T>(text: T) -> Self
     where
@@ -21,6 +41,8 @@ impl PythonizeError {
     {
         Self {
             inner: Box::new(ErrorImpl::Message(text.to_string())),
+            path: None,
+            context: None,
         }
     }
 
@@ -30,43 +52,216 @@ impl PythonizeError {
     {
         Self {
             inner: Box::new(ErrorImpl::UnsupportedType(t.to_string())),
+            path: None,
+            context: None,
+        }
+    }
+
+    pub(crate) fn unexpected_type<T>(t: T) -> Self
+    where
+        T: ToString,
+    {
+        Self {
+            inner: Box::new(ErrorImpl::UnexpectedType(t.to_string())),
+            path: None,
+            context: None,
         }
     }
 
     pub(crate) fn dict_key_not_string() -> Self {
         Self {
             inner: Box::new(ErrorImpl::DictKeyNotString),
+            path: None,
+            context: None,
         }
     }
 
     pub(crate) fn incorrect_sequence_length(expected: usize, got: usize) -> Self {
         Self {
             inner: Box::new(ErrorImpl::IncorrectSequenceLength { expected, got }),
+            path: None,
+            context: None,
         }
     }
 
     pub(crate) fn invalid_enum_type() -> Self {
         Self {
             inner: Box::new(ErrorImpl::InvalidEnumType),
+            path: None,
+            context: None,
         }
     }
 
     pub(crate) fn invalid_length_enum() -> Self {
         Self {
             inner: Box::new(ErrorImpl::InvalidLengthEnum),
+            path: None,
+            context: None,
         }
     }
 
     pub(crate) fn invalid_length_char() -> Self {
         Self {
             inner: Box::new(ErrorImpl::InvalidLengthChar),
+            path: None,
+            context: None,
+        }
+    }
+
+    pub(crate) fn depth_limit_exceeded(max_depth: usize) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::DepthLimitExceeded(max_depth)),
+            path: None,
+            context: None,
+        }
+    }
+
+    pub(crate) fn circular_reference() -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::CircularReference),
+            path: None,
+            context: None,
+        }
+    }
+
+    pub(crate) fn limit_exceeded(limit: Limit, max: usize, got: usize) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::LimitExceeded { limit, max, got }),
+            path: None,
+            context: None,
+        }
+    }
+
+    pub(crate) fn unknown_key(path: Path) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::UnknownKey(path)),
+            path: None,
+            context: None,
+        }
+    }
+
+    pub(crate) fn multiple(errors: Vec<PythonizeError>) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::Multiple(errors)),
+            path: None,
+            context: None,
+        }
+    }
+
+    /// Whether the error is about the shape or type of an input, without saying which input
+    /// or what was expected of it
+    pub(crate) fn needs_context(&self) -> bool {
+        self.context.is_none()
+            && self.path.is_none()
+            && matches!(
+                self.inner.as_ref(),
+                ErrorImpl::UnsupportedType(_)
+                    | ErrorImpl::UnexpectedType(_)
+                    | ErrorImpl::DictKeyNotString
+                    | ErrorImpl::IncorrectSequenceLength { .. }
+                    | ErrorImpl::InvalidEnumType
+                    | ErrorImpl::InvalidLengthEnum
+                    | ErrorImpl::InvalidLengthChar
+            )
+    }
+
+    pub(crate) fn with_context(mut self, value: String, expected: String) -> Self {
+        self.context = Some(Box::new(Context { value, expected }));
+        self
+    }
+
+    /// Locate the error inside the value at `path`
+    pub(crate) fn at(mut self, mut path: Path) -> Self {
+        if let Some(relative) = self.path.take() {
+            path.append(*relative);
+        }
+        self.path = Some(Box::new(path));
+        self
+    }
+
+    /// Locate the error inside the item at `segment` of a container
+    pub(crate) fn prepend(mut self, segment: PathSegment) -> Self {
+        self.path.get_or_insert_with(Box::default).prepend(segment);
+        self
+    }
+
+    /// What went wrong
+    pub fn kind(&self) -> ErrorKind<'_> {
+        match self.inner.as_ref() {
+            ErrorImpl::PyErr(e) => ErrorKind::PyErr(e),
+            ErrorImpl::Message(s) => ErrorKind::Message(s),
+            ErrorImpl::UnsupportedType(s) => ErrorKind::UnsupportedType(s),
+            ErrorImpl::UnexpectedType(s) => ErrorKind::UnexpectedType(s),
+            ErrorImpl::DictKeyNotString => ErrorKind::DictKeyNotString,
+            ErrorImpl::IncorrectSequenceLength { expected, got } => {
+                ErrorKind::IncorrectSequenceLength {
+                    expected: *expected,
+                    got: *got,
+                }
+            }
+            ErrorImpl::InvalidEnumType => ErrorKind::InvalidEnumType,
+            ErrorImpl::InvalidLengthEnum => ErrorKind::InvalidLengthEnum,
+            ErrorImpl::InvalidLengthChar => ErrorKind::InvalidLengthChar,
+            ErrorImpl::DepthLimitExceeded(max) => ErrorKind::DepthLimitExceeded(*max),
+            ErrorImpl::CircularReference => ErrorKind::CircularReference,
+            ErrorImpl::LimitExceeded { limit, max, got } => ErrorKind::LimitExceeded {
+                limit: *limit,
+                max: *max,
+                got: *got,
+            },
+            ErrorImpl::UnknownKey(path) => ErrorKind::UnknownKey(path),
+            ErrorImpl::Multiple(errors) => ErrorKind::Multiple(errors),
+            ErrorImpl::InvalidType {
+                unexpected,
+                expected,
+            } => ErrorKind::InvalidType {
+                unexpected,
+                expected,
+            },
+            ErrorImpl::InvalidValue {
+                unexpected,
+                expected,
+            } => ErrorKind::InvalidValue {
+                unexpected,
+                expected,
+            },
+            ErrorImpl::InvalidLength { len, expected } => ErrorKind::InvalidLength {
+                len: *len,
+                expected,
+            },
+            ErrorImpl::UnknownVariant { variant, expected } => {
+                ErrorKind::UnknownVariant { variant, expected }
+            }
+            ErrorImpl::UnknownField { field, expected } => {
+                ErrorKind::UnknownField { field, expected }
+            }
+            ErrorImpl::MissingField(field) => ErrorKind::MissingField(field),
+            ErrorImpl::DuplicateField(field) => ErrorKind::DuplicateField(field),
+        }
+    }
+
+    /// The location of the value which caused the error, such as `servers[0].port`.
+    ///
+    /// This is the empty path if the error is about the input as a whole, or if it did not come
+    /// from a `Depythonizer` or `Pythonizer`.
+    pub fn path(&self) -> &Path {
+        self.path.as_deref().unwrap_or(&ROOT)
+    }
+
+    /// The individual errors, if several were collected, or else just this error.
+    ///
+    /// See [`Depythonizer::collect_errors`](crate::Depythonizer::collect_errors).
+    pub fn errors(&self) -> &[PythonizeError] {
+        match self.inner.as_ref() {
+            ErrorImpl::Multiple(errors) => errors,
+            _ => std::slice::from_ref(self),
         }
     }
 }
 
 /// Error codes for problems that can occur when serializing/deserializing Python objects
 #[derive(Debug)]
-pub enum ErrorImpl {
+pub(crate) enum ErrorImpl {
     /// An error originating from the Python runtime
     PyErr(PyErr),
     /// Generic error message
@@ -85,6 +280,134 @@ pub enum ErrorImpl {
     InvalidLengthEnum,
     /// Expected a `char`, but got a Python str that was not length 1
     InvalidLengthChar,
+    /// Containers were nested deeper than the configured maximum depth
+    DepthLimitExceeded(usize),
+    /// A container was found inside itself
+    CircularReference,
+    /// The input exceeded one of the limits configured with `Depythonizer::limits`
+    LimitExceeded {
+        limit: Limit,
+        max: usize,
+        got: usize,
+    },
+    /// A key which the target type ignored, with `UnknownKeys::Error`
+    UnknownKey(Path),
+    /// Every error found with `Depythonizer::collect_errors`, each with its path
+    Multiple(Vec<PythonizeError>),
+    /// A value of the wrong type, such as a str where the target type expected an int
+    InvalidType {
+        unexpected: String,
+        expected: String,
+    },
+    /// A value of the right type but an invalid value, such as a negative int for `u8`
+    InvalidValue {
+        unexpected: String,
+        expected: String,
+    },
+    /// A sequence or mapping with the wrong number of items
+    InvalidLength { len: usize, expected: String },
+    /// An enum variant which the target type does not have
+    UnknownVariant {
+        variant: String,
+        expected: &'static [&'static str],
+    },
+    /// A struct field which the target type does not have, with `#[serde(deny_unknown_fields)]`
+    UnknownField {
+        field: String,
+        expected: &'static [&'static str],
+    },
+    /// A struct field which is required by the target type but was not given
+    MissingField(&'static str),
+    /// A struct field which was given more than once
+    DuplicateField(&'static str),
+}
+
+/// What went wrong in a [`PythonizeError`], see [`PythonizeError::kind`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ErrorKind<'a> {
+    /// An error originating from the Python runtime
+    PyErr(&'a PyErr),
+    /// Generic error message
+    Message(&'a str),
+    /// A Python type not supported by the deserializer
+    UnsupportedType(&'a str),
+    /// A `PyAny` object that failed to downcast to an expected Python type
+    UnexpectedType(&'a str),
+    /// Dict keys should be strings to deserialize to struct fields
+    DictKeyNotString,
+    /// Sequence length did not match expected tuple or tuple struct length.
+    IncorrectSequenceLength { expected: usize, got: usize },
+    /// Enum variants should either be dict (tagged) or str (variant)
+    InvalidEnumType,
+    /// Tagged enum variants should be a dict with exactly 1 key
+    InvalidLengthEnum,
+    /// Expected a `char`, but got a Python str that was not length 1
+    InvalidLengthChar,
+    /// Containers were nested deeper than the configured maximum depth
+    DepthLimitExceeded(usize),
+    /// A container was found inside itself
+    CircularReference,
+    /// The input exceeded one of the limits configured with `Depythonizer::limits`
+    LimitExceeded {
+        limit: Limit,
+        max: usize,
+        got: usize,
+    },
+    /// A key which the target type ignored, with `UnknownKeys::Error`
+    UnknownKey(&'a Path),
+    /// Every error found with `Depythonizer::collect_errors`, each with its path
+    Multiple(&'a [PythonizeError]),
+    /// A value of the wrong type, such as a str where the target type expected an int
+    InvalidType {
+        unexpected: &'a str,
+        expected: &'a str,
+    },
+    /// A value of the right type but an invalid value, such as a negative int for `u8`
+    InvalidValue {
+        unexpected: &'a str,
+        expected: &'a str,
+    },
+    /// A sequence or mapping with the wrong number of items
+    InvalidLength { len: usize, expected: &'a str },
+    /// An enum variant which the target type does not have
+    UnknownVariant {
+        variant: &'a str,
+        expected: &'static [&'static str],
+    },
+    /// A struct field which the target type does not have, with `#[serde(deny_unknown_fields)]`
+    UnknownField {
+        field: &'a str,
+        expected: &'static [&'static str],
+    },
+    /// A struct field which is required by the target type but was not given
+    MissingField(&'static str),
+    /// A struct field which was given more than once
+    DuplicateField(&'static str),
+}
+
+/// The kinds of limit which can be configured with `Depythonizer::limits`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// Total number of objects nested in the input
+    Nodes,
+    /// Number of items in a sequence, set or mapping
+    Length,
+    /// Length of a str or bytes object
+    StrLength,
+    /// Bit length of an int
+    IntBits,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Nodes => "number of objects",
+            Limit::Length => "container length",
+            Limit::StrLength => "str or bytes length",
+            Limit::IntBits => "int bit length",
+        })
+    }
 }
 
 impl error::Error for PythonizeError {}
@@ -105,7 +428,83 @@ impl Display for PythonizeError {
                 f.write_str("expected tagged enum dict to have exactly 1 key")
             }
             ErrorImpl::InvalidLengthChar => f.write_str("expected a str of length 1 for char"),
+            ErrorImpl::DepthLimitExceeded(max_depth) => {
+                write!(f, "maximum nesting depth of {} exceeded", max_depth)
+            }
+            ErrorImpl::CircularReference => f.write_str("circular reference detected"),
+            ErrorImpl::LimitExceeded { limit, max, got } => {
+                write!(f, "{} ({}) exceeds the limit of {}", limit, got, max)
+            }
+            ErrorImpl::UnknownKey(path) => write!(f, "unknown key {}", path),
+            ErrorImpl::InvalidType {
+                unexpected,
+                expected,
+            } => write!(f, "invalid type: {}, expected {}", unexpected, expected),
+            ErrorImpl::InvalidValue {
+                unexpected,
+                expected,
+            } => write!(f, "invalid value: {}, expected {}", unexpected, expected),
+            ErrorImpl::InvalidLength { len, expected } => {
+                write!(f, "invalid length {}, expected {}", len, expected)
+            }
+            ErrorImpl::UnknownVariant { variant, expected } => {
+                write!(f, "unknown variant `{}`, ", variant)?;
+                write_one_of(f, expected, "variants")?;
+                write_suggestion(f, variant, expected)
+            }
+            ErrorImpl::UnknownField { field, expected } => {
+                write!(f, "unknown field `{}`, ", field)?;
+                write_one_of(f, expected, "fields")?;
+                write_suggestion(f, field, expected)
+            }
+            ErrorImpl::MissingField(field) => write!(f, "missing field `{}`", field),
+            ErrorImpl::DuplicateField(field) => write!(f, "duplicate field `{}`", field),
+            ErrorImpl::Multiple(errors) => {
+                let plural = if errors.len() == 1 { "" } else { "s" };
+                write!(f, "{} validation error{}", errors.len(), plural)?;
+                for error in errors {
+                    write!(f, "\n{}", error.path())?;
+                    for line in error.to_string().lines() {
+                        write!(f, "\n  {}", line)?;
+                    }
+                }
+                Ok(())
+            }
+        }?;
+        if let Some(context) = &self.context {
+            write!(f, " (got {}, expected {})", context.value, context.expected)?;
         }
+        Ok(())
+    }
+}
+
+/// Write the names which were expected, as serde does
+fn write_one_of(f: &mut fmt::Formatter<'_>, names: &[&str], what: &str) -> fmt::Result {
+    match names {
+        [] => write!(f, "there are no {}", what),
+        [name] => write!(f, "expected `{}`", name),
+        [a, b] => write!(f, "expected `{}` or `{}`", a, b),
+        _ => {
+            f.write_str("expected one of ")?;
+            for (i, name) in names.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "`{}`", name)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Suggest the name among several which `name` was likely a typo of
+fn write_suggestion(f: &mut fmt::Formatter<'_>, name: &str, names: &[&str]) -> fmt::Result {
+    if names.len() < 2 {
+        return Ok(());
+    }
+    match rename::similar_name(name, names) {
+        Some(similar) => write!(f, " (did you mean `{}`?)", similar),
+        None => Ok(()),
     }
 }
 
@@ -122,6 +521,8 @@ impl ser::Error for PythonizeError {
     {
         Self {
             inner: Box::new(ErrorImpl::Message(msg.to_string())),
+            path: None,
+            context: None,
         }
     }
 }
@@ -133,6 +534,79 @@ impl de::Error for PythonizeError {
     {
         Self {
             inner: Box::new(ErrorImpl::Message(msg.to_string())),
+            path: None,
+            context: None,
+        }
+    }
+
+    fn invalid_type(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::InvalidType {
+                unexpected: unexp.to_string(),
+                expected: exp.to_string(),
+            }),
+            path: None,
+            context: None,
+        }
+    }
+
+    fn invalid_value(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::InvalidValue {
+                unexpected: unexp.to_string(),
+                expected: exp.to_string(),
+            }),
+            path: None,
+            context: None,
+        }
+    }
+
+    fn invalid_length(len: usize, exp: &dyn de::Expected) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::InvalidLength {
+                len,
+                expected: exp.to_string(),
+            }),
+            path: None,
+            context: None,
+        }
+    }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::UnknownVariant {
+                variant: variant.to_string(),
+                expected,
+            }),
+            path: None,
+            context: None,
+        }
+    }
+
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::UnknownField {
+                field: field.to_string(),
+                expected,
+            }),
+            path: None,
+            context: None,
+        }
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::MissingField(field)),
+            path: None,
+            context: None,
+        }
+    }
+
+    fn duplicate_field(field: &'static str) -> Self {
+        Self {
+            inner: Box::new(ErrorImpl::DuplicateField(field)),
+            path: None,
+            context: None,
         }
     }
 }
@@ -149,6 +623,8 @@ impl From<PyErr> for PythonizeError {
     fn from(other: PyErr) -> Self {
         Self {
             inner: Box::new(ErrorImpl::PyErr(other)),
+            path: None,
+            context: None,
         }
     }
 }
@@ -158,6 +634,8 @@ impl<'a, 'py> From<DowncastError<'a, 'py>> for PythonizeError {
     fn from(other: DowncastError<'a, 'py>) -> Self {
         Self {
             inner: Box::new(ErrorImpl::UnexpectedType(other.to_string())),
+            path: None,
+            context: None,
         }
     }
 }
@@ -167,23 +645,15 @@ impl<'py> From<DowncastIntoError<'py>> for PythonizeError {
     fn from(other: DowncastIntoError<'py>) -> Self {
         Self {
             inner: Box::new(ErrorImpl::UnexpectedType(other.to_string())),
+            path: None,
+            context: None,
         }
     }
 }
 
-/// Convert a `PythonizeError` to a Python exception
+/// Convert a `PythonizeError` to a Python exception, see [`crate::exceptions`]
 impl From<PythonizeError> for PyErr {
     fn from(other: PythonizeError) -> Self {
-        match *other.inner {
-            ErrorImpl::PyErr(e) => e,
-            ErrorImpl::Message(e) => PyException::new_err(e),
-            ErrorImpl::UnsupportedType(_)
-            | ErrorImpl::UnexpectedType(_)
-            | ErrorImpl::DictKeyNotString
-            | ErrorImpl::InvalidEnumType => PyTypeError::new_err(other.to_string()),
-            ErrorImpl::IncorrectSequenceLength { .. }
-            | ErrorImpl::InvalidLengthEnum
-            | ErrorImpl::InvalidLengthChar => PyValueError::new_err(other.to_string()),
-        }
+        exceptions::to_pyerr(other)
     }
 }
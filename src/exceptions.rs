@@ -0,0 +1,471 @@
+//! Python exceptions raised for [`PythonizeError`]s.
+//!
+//! Each exception class derives from both `PythonizeError` and the builtin exception which best
+//! describes the problem, so `except TypeError` keeps working while `except PythonizeError`
+//! catches every conversion failure:
+//!
+//! ```text
+//! Exception
+//!  +-- PythonizeError
+//!       +-- DepythonizeTypeError (also TypeError)
+//!       +-- DepythonizeValueError (also ValueError)
+//!       +-- DepythonizeKeyError (also KeyError)
+//!       +-- PythonizeRecursionError (also RecursionError)
+//!
+//! UserWarning
+//!  +-- PythonizeWarning
+//! ```
+//!
+//! `PythonizeWarning` is emitted for unknown keys and lossy conversions, see
+//! [`UnknownKeys::Warn`](crate::UnknownKeys::Warn) and
+//! [`Depythonizer::warn_lossy`](crate::Depythonizer::warn_lossy).
+//!
+//! The classes are created when first needed. Besides the `path` of the value which caused it,
+//! see [`PythonizeError::path`], an exception has `expected` and `got` attributes where these
+//! are known, or else `None`. Use [`register`] to make the classes importable from a module,
+//! and [`set_hook`] to raise different exceptions instead.
+
+use std::sync::{PoisonError, RwLock};
+
+use pyo3::exceptions::{
+    PyException, PyKeyError, PyRecursionError, PyRuntimeError, PyTypeError, PyUserWarning,
+    PyValueError,
+};
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{
+    PyAnyMethods, PyDict, PyDictMethods, PyList, PyModule, PyModuleMethods, PyStringMethods,
+    PyTuple, PyType,
+};
+use pyo3::{Bound, IntoPyObject, Py, PyAny, PyErr, PyResult, PyTypeInfo, Python};
+
+use crate::error::{ErrorImpl, PythonizeError};
+use crate::path::Path;
+
+/// A function converting errors to Python exceptions, see [`set_hook`]
+pub type ErrorHook = fn(PythonizeError) -> PyErr;
+
+static HOOK: RwLock<Option<ErrorHook>> = RwLock::new(None);
+
+/// The classes, in the order of [`ExceptionClass::ALL`]
+static CLASSES: GILOnceCell<Vec<Py<PyType>>> = GILOnceCell::new();
+
+/// The name of the module the classes were registered in, see [`register`]
+static REGISTERED_MODULE: GILOnceCell<String> = GILOnceCell::new();
+
+/// The Python classes of the exceptions raised for `PythonizeError`s, and of warnings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ExceptionClass {
+    /// The base class, raised for errors with only a message
+    PythonizeError,
+    /// Raised for objects of the wrong type
+    DepythonizeTypeError,
+    /// Raised for objects of the right type with an invalid value
+    DepythonizeValueError,
+    /// Raised for missing and unknown struct fields
+    DepythonizeKeyError,
+    /// Raised when containers are nested too deeply
+    PythonizeRecursionError,
+    /// The warning category for unknown keys and lossy conversions, not an error
+    PythonizeWarning,
+}
+
+impl ExceptionClass {
+    /// Every class, base class first
+    pub const ALL: [ExceptionClass; 6] = [
+        ExceptionClass::PythonizeError,
+        ExceptionClass::DepythonizeTypeError,
+        ExceptionClass::DepythonizeValueError,
+        ExceptionClass::DepythonizeKeyError,
+        ExceptionClass::PythonizeRecursionError,
+        ExceptionClass::PythonizeWarning,
+    ];
+
+    /// The name of the class in Python
+    pub fn name(self) -> &'static str {
+        match self {
+            ExceptionClass::PythonizeError => "PythonizeError",
+            ExceptionClass::DepythonizeTypeError => "DepythonizeTypeError",
+            ExceptionClass::DepythonizeValueError => "DepythonizeValueError",
+            ExceptionClass::DepythonizeKeyError => "DepythonizeKeyError",
+            ExceptionClass::PythonizeRecursionError => "PythonizeRecursionError",
+            ExceptionClass::PythonizeWarning => "PythonizeWarning",
+        }
+    }
+
+    /// The class raised for `error` by default
+    pub fn of(error: &PythonizeError) -> Self {
+        match error.inner.as_ref() {
+            ErrorImpl::UnsupportedType(_)
+            | ErrorImpl::UnexpectedType(_)
+            | ErrorImpl::DictKeyNotString
+            | ErrorImpl::InvalidEnumType
+            | ErrorImpl::InvalidType { .. } => ExceptionClass::DepythonizeTypeError,
+            ErrorImpl::IncorrectSequenceLength { .. }
+            | ErrorImpl::InvalidLengthEnum
+            | ErrorImpl::InvalidLengthChar
+            | ErrorImpl::CircularReference
+            | ErrorImpl::LimitExceeded { .. }
+            | ErrorImpl::UnknownKey(_)
+            | ErrorImpl::InvalidValue { .. }
+            | ErrorImpl::InvalidLength { .. }
+            | ErrorImpl::UnknownVariant { .. }
+            | ErrorImpl::DuplicateField(_)
+            | ErrorImpl::Multiple(_) => ExceptionClass::DepythonizeValueError,
+            ErrorImpl::MissingField(_) | ErrorImpl::UnknownField { .. } => {
+                ExceptionClass::DepythonizeKeyError
+            }
+            ErrorImpl::DepthLimitExceeded(_) => ExceptionClass::PythonizeRecursionError,
+            ErrorImpl::PyErr(_) | ErrorImpl::Message(_) => ExceptionClass::PythonizeError,
+        }
+    }
+
+    /// The Python class, created the first time it is needed
+    pub fn type_object(self, py: Python<'_>) -> PyResult<Bound<'_, PyType>> {
+        let classes = CLASSES.get_or_try_init(py, || create_classes(py))?;
+        let index = ExceptionClass::ALL.iter().position(|&c| c == self).unwrap();
+        Ok(classes[index].bind(py).clone())
+    }
+
+    /// The builtin exception the class derives from, besides `PythonizeError` for errors
+    fn builtin(self, py: Python<'_>) -> Bound<'_, PyType> {
+        match self {
+            ExceptionClass::PythonizeError => PyException::type_object(py),
+            ExceptionClass::DepythonizeTypeError => PyTypeError::type_object(py),
+            ExceptionClass::DepythonizeValueError => PyValueError::type_object(py),
+            ExceptionClass::DepythonizeKeyError => PyKeyError::type_object(py),
+            ExceptionClass::PythonizeRecursionError => PyRecursionError::type_object(py),
+            ExceptionClass::PythonizeWarning => PyUserWarning::type_object(py),
+        }
+    }
+}
+
+fn create_classes(py: Python<'_>) -> PyResult<Vec<Py<PyType>>> {
+    let mut classes: Vec<Py<PyType>> = Vec::with_capacity(ExceptionClass::ALL.len());
+    for class in ExceptionClass::ALL {
+        let bases = match classes.first() {
+            Some(base) if class != ExceptionClass::PythonizeWarning => {
+                PyTuple::new(py, [base.bind(py).as_any(), class.builtin(py).as_any()])?
+            }
+            _ => PyTuple::new(py, [class.builtin(py)])?,
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("__module__", "pythonize")?;
+        dict.set_item("path", PyTuple::empty(py))?;
+        dict.set_item("expected", py.None())?;
+        dict.set_item("got", py.None())?;
+        let class = PyType::type_object(py).call1((class.name(), bases, dict))?;
+        classes.push(class.downcast_into::<PyType>()?.unbind());
+    }
+    Ok(classes)
+}
+
+/// Add the exception classes to `module`, so that Python code can catch them by name.
+///
+/// This sets the `__module__` of the classes to the name of `module`. The classes are shared
+/// by the whole process, so registering them in a second, different module is an error.
+pub fn register(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    let name = module.name()?.to_cow()?.into_owned();
+    let registered = REGISTERED_MODULE.get_or_init(py, || name.clone());
+    if *registered != name {
+        return Err(PyRuntimeError::new_err(format!(
+            "pythonize exceptions are already registered in module {}",
+            registered
+        )));
+    }
+    for class in ExceptionClass::ALL {
+        let ty = class.type_object(py)?;
+        ty.setattr("__module__", module.name()?)?;
+        module.add(class.name(), ty)?;
+    }
+    Ok(())
+}
+
+/// Convert every `PythonizeError` to a Python exception with `hook`, rather than
+/// [`default_pyerr`], or restore the default with `None`.
+///
+/// This applies to the whole process, so it should be set once, when the module is imported.
+pub fn set_hook(hook: Option<ErrorHook>) {
+    *HOOK.write().unwrap_or_else(PoisonError::into_inner) = hook;
+}
+
+/// Convert `error` to a Python exception with the hook, if one is set
+pub(crate) fn to_pyerr(error: PythonizeError) -> PyErr {
+    let hook = *HOOK.read().unwrap_or_else(PoisonError::into_inner);
+    match hook {
+        Some(hook) => hook(error),
+        None => default_pyerr(error),
+    }
+}
+
+/// Convert `error` to an instance of its [`ExceptionClass`], or the exception it came from
+/// if it was raised in Python
+pub fn default_pyerr(error: PythonizeError) -> PyErr {
+    Python::with_gil(|py| {
+        let path = error.path.clone();
+        // only instances of the classes above get attributes, other exceptions only get a note
+        let (err, own) = match *error.inner {
+            ErrorImpl::PyErr(e) => (e, false),
+            _ => match ExceptionClass::of(&error).type_object(py) {
+                Ok(ty) => {
+                    let err = PyErr::from_type(ty, error.to_string());
+                    // the message describes the error, so the attributes are only a convenience
+                    let _ = add_details(py, &err, &error);
+                    (err, true)
+                }
+                Err(e) => (e, false),
+            },
+        };
+        if let Some(path) = path {
+            if own {
+                let _ = err.value(py).setattr("path", &*path);
+            }
+            let _ = add_note(py, &err, &path);
+        }
+        err
+    })
+}
+
+/// Set the `expected` and `got` attributes, and the `errors` of collected errors
+fn add_details(py: Python<'_>, err: &PyErr, error: &PythonizeError) -> PyResult<()> {
+    let value = err.value(py);
+    let set = |expected: Bound<'_, PyAny>, got: Bound<'_, PyAny>| -> PyResult<()> {
+        value.setattr("expected", expected)?;
+        value.setattr("got", got)
+    };
+    match error.inner.as_ref() {
+        ErrorImpl::InvalidType {
+            unexpected,
+            expected,
+        }
+        | ErrorImpl::InvalidValue {
+            unexpected,
+            expected,
+        } => set(
+            expected.into_pyobject(py)?.into_any(),
+            unexpected.into_pyobject(py)?.into_any(),
+        ),
+        ErrorImpl::InvalidLength { len, expected } => set(
+            expected.into_pyobject(py)?.into_any(),
+            len.into_pyobject(py)?.into_any(),
+        ),
+        ErrorImpl::IncorrectSequenceLength { expected, got }
+        | ErrorImpl::LimitExceeded {
+            max: expected, got, ..
+        } => set(
+            expected.into_pyobject(py)?.into_any(),
+            got.into_pyobject(py)?.into_any(),
+        ),
+        ErrorImpl::UnknownVariant {
+            variant: name,
+            expected,
+        }
+        | ErrorImpl::UnknownField {
+            field: name,
+            expected,
+        } => set(
+            PyTuple::new(py, *expected)?.into_any(),
+            name.into_pyobject(py)?.into_any(),
+        ),
+        ErrorImpl::Multiple(errors) => value.setattr("errors", error_dicts(py, errors)?),
+        _ => match &error.context {
+            Some(context) => set(
+                context.expected.as_str().into_pyobject(py)?.into_any(),
+                context.value.as_str().into_pyobject(py)?.into_any(),
+            ),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Attach `path` to the exception as a PEP 678 note, which tracebacks show
+fn add_note(py: Python<'_>, err: &PyErr, path: &Path) -> PyResult<()> {
+    let value = err.value(py);
+    let note = format!("at {}", path);
+    // `BaseException.add_note` is only available from Python 3.11, which shows the notes
+    match value.getattr("__notes__") {
+        Ok(notes) => notes.call_method1("append", (note,)).map(drop),
+        Err(_) => value.setattr("__notes__", PyList::new(py, [note])?),
+    }
+}
+
+/// A dict with the `loc` and `msg` of each error, like pydantic's `ValidationError.errors()`
+fn error_dicts<'py>(
+    py: Python<'py>,
+    errors: &[PythonizeError],
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    errors
+        .iter()
+        .map(|error| {
+            let dict = PyDict::new(py);
+            dict.set_item("loc", error.path())?;
+            dict.set_item("msg", error.to_string())?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::num::NonZeroU8;
+
+    use pyo3::exceptions::{PyException, PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
+    use pyo3::ffi::c_str;
+    use pyo3::types::{PyAnyMethods, PyDict, PyModule, PyTypeMethods};
+    use pyo3::{PyErr, Python};
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::depythonize;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    #[allow(dead_code)]
+    struct Item {
+        name: String,
+        size: NonZeroU8,
+    }
+
+    fn error_for(py: Python<'_>, source: &str) -> PyErr {
+        let code = std::ffi::CString::new(source).unwrap();
+        let obj = py.eval(&code, None, None).unwrap();
+        depythonize::<Item>(&obj).unwrap_err().into()
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        Python::with_gil(|py| {
+            let base = ExceptionClass::PythonizeError.type_object(py).unwrap();
+            assert!(base.is_subclass_of::<PyException>().unwrap());
+            for (class, builtin) in [
+                (
+                    ExceptionClass::DepythonizeTypeError,
+                    PyTypeError::type_object(py),
+                ),
+                (
+                    ExceptionClass::DepythonizeValueError,
+                    PyValueError::type_object(py),
+                ),
+                (
+                    ExceptionClass::DepythonizeKeyError,
+                    PyKeyError::type_object(py),
+                ),
+            ] {
+                let ty = class.type_object(py).unwrap();
+                assert!(ty.is_subclass(base.as_any()).unwrap());
+                assert!(ty.is_subclass(builtin.as_any()).unwrap());
+                assert_eq!(ty.name().unwrap().to_string(), class.name());
+            }
+
+            let err = error_for(py, "{'name': 1, 'size': 2}");
+            assert!(err.is_instance(py, &base));
+            assert!(err.is_instance_of::<PyTypeError>(py));
+            let got: String = err.value(py).getattr("got").unwrap().extract().unwrap();
+            assert_eq!(got, "1");
+        })
+    }
+
+    #[test]
+    fn test_attributes() {
+        Python::with_gil(|py| {
+            let err = error_for(py, "{'name': 'a', 'size': 2, 'colour': 3}");
+            let value = err.value(py);
+            let ty = ExceptionClass::DepythonizeKeyError.type_object(py).unwrap();
+            assert!(value.is_instance(&ty).unwrap());
+            let expected: Vec<String> = value.getattr("expected").unwrap().extract().unwrap();
+            assert_eq!(expected, ["name", "size"]);
+            let got: String = value.getattr("got").unwrap().extract().unwrap();
+            assert_eq!(got, "colour");
+
+            let err = error_for(py, "{'name': 'a', 'size': 0}");
+            let value = err.value(py);
+            let path: Vec<String> = value.getattr("path").unwrap().extract().unwrap();
+            assert_eq!(path, ["size"]);
+            let got: String = value.getattr("got").unwrap().extract().unwrap();
+            assert_eq!(got, "integer `0`");
+            assert!(err.is_instance_of::<PyValueError>(py));
+
+            // without details, the class attributes are used
+            let err = error_for(py, "{'name': 'a'}");
+            assert!(err.value(py).getattr("expected").unwrap().is_none());
+            assert!(err.value(py).getattr("got").unwrap().is_none());
+        })
+    }
+
+    #[test]
+    fn test_foreign_exception() {
+        Python::with_gil(|py| {
+            let obj = py.eval(c_str!("{'name': object()}"), None, None).unwrap();
+            let mut de = crate::Depythonizer::from_object(&obj)
+                .fallback(|_| Err(PyRuntimeError::new_err("no substitute")));
+            let err: PyErr = serde_json::Value::deserialize(&mut de).unwrap_err().into();
+            assert!(err.is_instance_of::<PyRuntimeError>(py));
+
+            // raised by Python code, so only the note is added
+            let value = err.value(py);
+            assert!(!value.hasattr("path").unwrap());
+            let notes: Vec<String> = value.getattr("__notes__").unwrap().extract().unwrap();
+            assert_eq!(notes, ["at name"]);
+        })
+    }
+
+    #[test]
+    fn test_register() {
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "mymodule").unwrap();
+            register(&module).unwrap();
+            for class in ExceptionClass::ALL {
+                let ty = module.getattr(class.name()).unwrap();
+                assert!(ty.is(class.type_object(py).unwrap()));
+                let name: String = ty.getattr("__module__").unwrap().extract().unwrap();
+                assert_eq!(name, "mymodule");
+            }
+
+            // registering again in the same module is allowed, but not in another one
+            register(&module).unwrap();
+            let other = PyModule::new(py, "othermodule").unwrap();
+            assert!(register(&other).is_err());
+            assert!(other.getattr("PythonizeError").is_err());
+        })
+    }
+
+    thread_local! {
+        /// Whether `scoped_hook` applies, so that tests on other threads are unaffected
+        static HOOKED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    fn scoped_hook(error: PythonizeError) -> PyErr {
+        if HOOKED.with(Cell::get) {
+            PyRuntimeError::new_err(format!("hooked: {}", error))
+        } else {
+            default_pyerr(error)
+        }
+    }
+
+    #[test]
+    fn test_hook() {
+        Python::with_gil(|py| {
+            set_hook(Some(scoped_hook));
+            HOOKED.with(|h| h.set(true));
+            let hooked = error_for(py, "{'name': 'a'}");
+            HOOKED.with(|h| h.set(false));
+            let err = error_for(py, "{'name': 'a'}");
+            set_hook(None);
+
+            assert!(hooked.is_instance_of::<PyRuntimeError>(py));
+            assert_eq!(hooked.value(py).to_string(), "hooked: missing field `size`");
+            assert!(err.is_instance_of::<PyKeyError>(py));
+
+            let locals = PyDict::new(py);
+            locals.set_item("err", err.value(py)).unwrap();
+            let message: String = py
+                .eval(pyo3::ffi::c_str!("str(err)"), None, Some(&locals))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(message, "'missing field `size`'");
+        })
+    }
+}
@@ -1,12 +1,27 @@
 #![doc = include_str!("../README.md")]
 
+mod collect;
 mod de;
 mod error;
+pub mod exceptions;
+mod path;
+pub mod pybacked;
+mod rename;
 mod ser;
+mod shared;
+mod value;
 
-pub use crate::de::{depythonize, Depythonizer};
-pub use crate::error::{PythonizeError, Result};
+pub use crate::de::{
+    depythonize, depythonize_with_ignored, validate, ClassTag, Coercions, Depythonizer, Limits,
+    Strictness, UnknownKeys,
+};
+pub use crate::error::{ErrorKind, Limit, PythonizeError, Result};
+pub use crate::path::{Path, PathSegment};
+pub use crate::rename::RenameRule;
 pub use crate::ser::{
     pythonize, pythonize_custom, PythonizeDefault, PythonizeListType, PythonizeMappingType,
     PythonizeNamedMappingType, PythonizeTypes, PythonizeUnnamedMappingAdapter, Pythonizer,
+    StructLayout,
 };
+pub use crate::shared::{Shared, SharedPointer};
+pub use crate::value::{from_value, to_value, PyValue};
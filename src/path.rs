@@ -0,0 +1,131 @@
+use std::convert::Infallible;
+use std::fmt::{self, Display};
+
+use pyo3::types::{PyAnyMethods, PyString, PyStringMethods, PyTuple};
+use pyo3::{Bound, IntoPyObject, PyAny, PyErr, Python};
+
+/// One step of a [`Path`] into a Python object
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// A key of a mapping or the name of an attribute, converted with `str()` if not a str
+    Key(String),
+    /// An index into a sequence or iterable
+    Index(usize),
+}
+
+impl PathSegment {
+    /// The segment for a key of a mapping
+    pub(crate) fn from_key(key: &Bound<'_, PyAny>) -> Self {
+        PathSegment::Key(match key.downcast::<PyString>() {
+            Ok(key) => key.to_string_lossy().into_owned(),
+            Err(_) => key
+                .str()
+                .map_or_else(|_| "?".to_string(), |s| s.to_string()),
+        })
+    }
+}
+
+/// The location of a value inside a Python object, such as `servers[0].port`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Path {
+    segments: Vec<PathSegment>,
+}
+
+impl Path {
+    /// Create a path from its segments, outermost first
+    pub const fn new(segments: Vec<PathSegment>) -> Self {
+        Self { segments }
+    }
+
+    /// The segments of the path, outermost first
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    /// Add a segment in front of the others, as an error leaves the container it occurred in
+    pub(crate) fn prepend(&mut self, segment: PathSegment) {
+        self.segments.insert(0, segment);
+    }
+
+    pub(crate) fn append(&mut self, other: Path) {
+        self.segments.extend(other.segments);
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &PathSegment {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = Infallible;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        Ok(match self {
+            PathSegment::Key(key) => key.into_pyobject(py)?.into_any(),
+            PathSegment::Index(index) => index.into_pyobject(py)?.into_any(),
+        })
+    }
+}
+
+/// A tuple of the keys (str) and indices (int) of the path, like pydantic's `loc`
+impl<'py> IntoPyObject<'py> for &Path {
+    type Target = PyTuple;
+    type Output = Bound<'py, PyTuple>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        PyTuple::new(py, &self.segments)
+    }
+}
+
+impl From<Vec<PathSegment>> for Path {
+    fn from(segments: Vec<PathSegment>) -> Self {
+        Self::new(segments)
+    }
+}
+
+/// Keys which are identifiers are written as attributes, and other keys in brackets.
+/// The empty path, referring to the object itself, is written as `.`
+impl Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.segments.is_empty() {
+            return f.write_str(".");
+        }
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if is_identifier(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(key)?;
+                }
+                PathSegment::Key(key) => write!(f, "[{:?}]", key)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Path, PathSegment};
+
+    #[test]
+    fn test_display() {
+        let key = |k: &str| PathSegment::Key(k.to_string());
+        assert_eq!(Path::default().to_string(), ".");
+        assert_eq!(
+            Path::new(vec![key("servers"), PathSegment::Index(0), key("port")]).to_string(),
+            "servers[0].port"
+        );
+        assert_eq!(
+            Path::new(vec![PathSegment::Index(1), key("a b"), key("_c1")]).to_string(),
+            "[1][\"a b\"]._c1"
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Serialize and deserialize [`PyBackedStr`] and [`PyBackedBytes`] with
+//! `#[serde(with = "pythonize::pybacked")]`.
+//!
+//! `Depythonizer` hands the Python object itself to these fields, so a `str` or `bytes` is
+//! referenced rather than copied. Other deserializers are not supported.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+
+use pyo3::pybacked::{PyBackedBytes, PyBackedStr};
+use pyo3::types::PyAnyMethods;
+use pyo3::{FromPyObject, Py, PyAny, Python};
+use serde::{de, Deserializer, Serializer};
+
+/// Newtype struct name used by this module to identify itself to `Depythonizer`
+pub(crate) const PYBACKED_TOKEN: &str = "$pythonize::private::PyBacked";
+
+thread_local! {
+    static DE_SLOT: RefCell<Option<Py<PyAny>>> = const { RefCell::new(None) };
+}
+
+/// Hand the Python object being deserialized to the `PyBacked` visitor
+pub(crate) fn set_de_slot(obj: Py<PyAny>) {
+    DE_SLOT.with(|s| *s.borrow_mut() = Some(obj));
+}
+
+fn take_de_slot() -> Option<Py<PyAny>> {
+    DE_SLOT.with(|s| s.borrow_mut().take())
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for pyo3::pybacked::PyBackedStr {}
+    impl Sealed for pyo3::pybacked::PyBackedBytes {}
+}
+
+/// Types which hold a reference to Python data, supported by this module
+pub trait PyBacked: for<'py> FromPyObject<'py> + private::Sealed {
+    #[doc(hidden)]
+    fn serialize_data<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+}
+
+impl PyBacked for PyBackedStr {
+    fn serialize_data<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+impl PyBacked for PyBackedBytes {
+    fn serialize_data<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self)
+    }
+}
+
+/// Serialize a `PyBackedStr` as a str, or a `PyBackedBytes` as bytes
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: PyBacked,
+    S: Serializer,
+{
+    value.serialize_data(serializer)
+}
+
+/// Deserialize a `PyBackedStr` from a `str`, or a `PyBackedBytes` from `bytes` or `bytearray`
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: PyBacked,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_newtype_struct(PYBACKED_TOKEN, PyBackedVisitor(PhantomData))
+}
+
+struct PyBackedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: PyBacked> de::Visitor<'de> for PyBackedVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a Python str or bytes object")
+    }
+
+    fn visit_newtype_struct<D>(self, _deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match take_de_slot() {
+            // the GIL is already held by `Depythonizer`
+            Some(obj) => Python::with_gil(|py| obj.bind(py).extract()).map_err(de::Error::custom),
+            None => Err(de::Error::custom(
+                "Python-backed data can only be deserialized from a Python object",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pyo3::ffi::c_str;
+    use pyo3::pybacked::{PyBackedBytes, PyBackedStr};
+    use pyo3::types::{PyAnyMethods, PyDict, PyDictMethods, PyString, PyStringMethods};
+    use pyo3::Python;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{depythonize, pythonize};
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Document {
+        #[serde(with = "crate::pybacked")]
+        text: PyBackedStr,
+        #[serde(with = "crate::pybacked")]
+        data: PyBackedBytes,
+    }
+
+    #[test]
+    fn test_pybacked_references_python_data() {
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(c_str!("{'text': 'hello', 'data': b'world'}"), None, None)
+                .unwrap();
+            let doc: Document = depythonize(&obj).unwrap();
+            assert_eq!(&*doc.text, "hello");
+            assert_eq!(&*doc.data, b"world");
+
+            let text = obj.get_item("text").unwrap();
+            let text = text.downcast::<PyString>().unwrap();
+            assert_eq!(doc.text.as_ptr(), text.to_cow().unwrap().as_ptr());
+
+            let back = pythonize(py, &doc).unwrap();
+            let back = back.downcast::<PyDict>().unwrap();
+            assert!(back.get_item("text").unwrap().unwrap().eq("hello").unwrap());
+        });
+    }
+
+    #[test]
+    fn test_pybacked_wrong_type() {
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(c_str!("{'text': 1, 'data': b''}"), None, None)
+                .unwrap();
+            assert!(depythonize::<Document>(&obj).is_err());
+        });
+    }
+
+    #[test]
+    fn test_pybacked_other_formats() {
+        let err = serde_json::from_str::<Document>(r#"{"text": "a", "data": []}"#).unwrap_err();
+        assert!(err.to_string().contains("Python object"));
+    }
+}
@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+
+/// A naming convention for struct fields and enum variants, applied at runtime.
+///
+/// The conventions are those of serde's `#[serde(rename_all = "...")]` attribute, assuming that
+/// fields are written in `snake_case` and variants in `PascalCase` in Rust. See
+/// [`Pythonizer::rename_fields`] and [`Depythonizer::rename_fields`].
+///
+/// [`Pythonizer::rename_fields`]: crate::Pythonizer::rename_fields
+/// [`Depythonizer::rename_fields`]: crate::Depythonizer::rename_fields
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RenameRule {
+    /// `lowercase`
+    LowerCase,
+    /// `UPPERCASE`
+    UpperCase,
+    /// `PascalCase`
+    PascalCase,
+    /// `camelCase`
+    CamelCase,
+    /// `snake_case`
+    SnakeCase,
+    /// `SCREAMING_SNAKE_CASE`
+    ScreamingSnakeCase,
+    /// `kebab-case`
+    KebabCase,
+    /// `SCREAMING-KEBAB-CASE`
+    ScreamingKebabCase,
+}
+
+impl RenameRule {
+    /// Rename a `snake_case` struct field
+    pub fn apply_to_field(self, field: &str) -> String {
+        match self {
+            RenameRule::LowerCase | RenameRule::SnakeCase => field.to_owned(),
+            RenameRule::UpperCase | RenameRule::ScreamingSnakeCase => field.to_ascii_uppercase(),
+            RenameRule::PascalCase => {
+                let mut pascal = String::with_capacity(field.len());
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            RenameRule::CamelCase => {
+                let pascal = RenameRule::PascalCase.apply_to_field(field);
+                lowercase_first(&pascal)
+            }
+            RenameRule::KebabCase => field.replace('_', "-"),
+            RenameRule::ScreamingKebabCase => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+
+    /// Rename a `PascalCase` enum variant
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            RenameRule::PascalCase => variant.to_owned(),
+            RenameRule::LowerCase => variant.to_ascii_lowercase(),
+            RenameRule::UpperCase => variant.to_ascii_uppercase(),
+            RenameRule::CamelCase => lowercase_first(variant),
+            RenameRule::SnakeCase => {
+                let mut snake = String::with_capacity(variant.len() + 4);
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            RenameRule::ScreamingSnakeCase => RenameRule::SnakeCase
+                .apply_to_variant(variant)
+                .to_ascii_uppercase(),
+            RenameRule::KebabCase => RenameRule::SnakeCase
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+            RenameRule::ScreamingKebabCase => RenameRule::ScreamingSnakeCase
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+        }
+    }
+}
+
+fn lowercase_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// The name among `names` which is renamed to `name` by `rename`, preferring an exact match
+pub(crate) fn original_name<F>(
+    name: &str,
+    names: &'static [&'static str],
+    rename: F,
+    case_insensitive: bool,
+) -> Option<&'static str>
+where
+    F: Fn(&'static str) -> Cow<'static, str>,
+{
+    if let Some(original) = names.iter().copied().find(|&n| rename(n) == name) {
+        return Some(original);
+    }
+    if case_insensitive {
+        let lowercase = |s: &str| s.chars().flat_map(char::to_lowercase).collect::<String>();
+        let name = lowercase(name);
+        return names
+            .iter()
+            .copied()
+            .find(|&n| lowercase(&rename(n)) == name);
+    }
+    None
+}
+
+/// The name among `names` most like `name`, if one is close enough to be a likely typo.
+///
+/// Names are compared ignoring case, `_` and `-`, so that a name in another naming convention
+/// still finds its match.
+pub(crate) fn similar_name<'n>(name: &str, names: &[&'n str]) -> Option<&'n str> {
+    let normalize = |s: &str| -> Vec<char> {
+        s.chars()
+            .filter(|&c| c != '_' && c != '-')
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let name = normalize(name);
+    let max_distance = (name.len() / 3).max(1);
+    names
+        .iter()
+        .map(|&n| (edit_distance(&name, &normalize(n)), n))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, n)| n)
+}
+
+/// The number of insertions, deletions, substitutions and swaps of adjacent characters which
+/// turn `a` into `b`
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    // d[i][j] is the distance between the first i characters of a and the first j of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in d[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(d[i - 2][j - 2] + 1);
+            }
+            d[i][j] = distance;
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::similar_name;
+    use super::RenameRule::{self, *};
+
+    #[test]
+    fn test_rename_rules() {
+        let rules: [(RenameRule, &str, &str); 8] = [
+            (LowerCase, "user_id", "httpstatus"),
+            (UpperCase, "USER_ID", "HTTPSTATUS"),
+            (PascalCase, "UserId", "HttpStatus"),
+            (CamelCase, "userId", "httpStatus"),
+            (SnakeCase, "user_id", "http_status"),
+            (ScreamingSnakeCase, "USER_ID", "HTTP_STATUS"),
+            (KebabCase, "user-id", "http-status"),
+            (ScreamingKebabCase, "USER-ID", "HTTP-STATUS"),
+        ];
+        for (rule, field, variant) in rules {
+            assert_eq!(rule.apply_to_field("user_id"), field, "{:?}", rule);
+            assert_eq!(rule.apply_to_variant("HttpStatus"), variant, "{:?}", rule);
+        }
+    }
+
+    #[test]
+    fn test_similar_name() {
+        let names = &["name", "size", "max_retries"];
+        assert_eq!(similar_name("nmae", names), Some("name"));
+        assert_eq!(similar_name("sise", names), Some("size"));
+        assert_eq!(similar_name("maxRetries", names), Some("max_retries"));
+        assert_eq!(similar_name("max_retrys", names), Some("max_retries"));
+        assert_eq!(similar_name("colour", names), None);
+        assert_eq!(similar_name("x", &[]), None);
+    }
+}
@@ -1,13 +1,21 @@
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::rc::Rc;
 
 use pyo3::types::{
-    PyDict, PyDictMethods, PyList, PyListMethods, PyMapping, PySequence, PyString, PyTuple,
-    PyTupleMethods,
+    PyAnyMethods, PyDict, PyDictMethods, PyList, PyListMethods, PyMapping, PySequence, PyString,
+    PyTuple, PyTupleMethods,
 };
 use pyo3::{Bound, BoundObject, IntoPyObject, PyAny, PyResult, Python};
 use serde::{ser, Serialize};
 
 use crate::error::{PythonizeError, Result};
+use crate::path::PathSegment;
+use crate::rename::RenameRule;
+use crate::shared::{self, SHARED_TOKEN};
 
 // TODO: move 'py lifetime into builder once GATs are available in MSRV
 /// Trait for types which can represent a Python mapping
@@ -178,17 +186,101 @@ where
     value.serialize(Pythonizer::custom::<P>(py))
 }
 
+/// How a `Pythonizer` represents structs and struct variants, see [`Pythonizer::struct_layout`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StructLayout {
+    /// A mapping from field names to values, built with [`PythonizeTypes::NamedMap`]
+    #[default]
+    Map,
+    /// A tuple of the field values, in declaration order
+    Tuple,
+    /// A sequence of the field values, in declaration order, built with [`PythonizeTypes::List`]
+    List,
+}
+
+/// The default maximum nesting depth of containers for `Pythonizer`
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// The default number of values serialized between checks for pending signals
+const DEFAULT_SIGNAL_CHECK_INTERVAL: usize = 1024;
+
 /// A structure that serializes Rust values into Python objects
-#[derive(Clone, Copy)]
 pub struct Pythonizer<'py, P> {
     py: Python<'py>,
+    options: PythonizerOptions<'py>,
     _types: PhantomData<P>,
 }
 
+/// The options of a `Pythonizer`, which apply to every nested value
+#[derive(Clone, Copy)]
+struct PythonizerOptions<'py> {
+    max_depth: usize,
+    signal_check_interval: usize,
+    variant_classes: Option<&'py Bound<'py, PyDict>>,
+    struct_layout: StructLayout,
+    rename_fields: Option<RenameRule>,
+    rename_variants: Option<RenameRule>,
+}
+
+impl Default for PythonizerOptions<'_> {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            signal_check_interval: DEFAULT_SIGNAL_CHECK_INTERVAL,
+            variant_classes: None,
+            struct_layout: StructLayout::Map,
+            rename_fields: None,
+            rename_variants: None,
+        }
+    }
+}
+
+impl<P> Clone for Pythonizer<'_, P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for Pythonizer<'_, P> {}
+
 impl<'py, P> From<Python<'py>> for Pythonizer<'py, P> {
     fn from(py: Python<'py>) -> Self {
         Self {
             py,
+            options: PythonizerOptions::default(),
+            _types: PhantomData,
+        }
+    }
+}
+
+/// Python objects already produced for `Shared` pointers, keyed by address
+type SharedObjects<'py> = HashMap<usize, (Rc<dyn Any>, Bound<'py, PyAny>)>;
+
+/// State shared by the serializers for all values nested inside the one given to a `Pythonizer`
+struct PythonizerState<'py> {
+    /// Number of nested values serialized so far
+    nodes: Cell<usize>,
+    /// The pointers are kept alive so that their addresses cannot be reused
+    shared: RefCell<SharedObjects<'py>>,
+    _active: shared::SerializingGuard,
+}
+
+/// A `Pythonizer` which is serializing a value, with the state shared by its nested values
+struct PythonizerContext<'py, P> {
+    py: Python<'py>,
+    options: PythonizerOptions<'py>,
+    state: Rc<PythonizerState<'py>>,
+    depth: usize,
+    _types: PhantomData<P>,
+}
+
+impl<P> Clone for PythonizerContext<'_, P> {
+    fn clone(&self) -> Self {
+        Self {
+            py: self.py,
+            options: self.options,
+            state: self.state.clone(),
+            depth: self.depth,
             _types: PhantomData,
         }
     }
@@ -206,17 +298,150 @@ impl<'py> Pythonizer<'py, PythonizeDefault> {
     }
 }
 
+impl<'py, P> Pythonizer<'py, P> {
+    /// Set the maximum nesting depth of containers, 128 by default.
+    ///
+    /// Deeper values fail with an error instead of overflowing the stack.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.options.max_depth = max_depth;
+        self
+    }
+
+    /// Set how many values are serialized between checks for pending signals, 1024 by default.
+    ///
+    /// A signal handler which raises, such as the default `SIGINT` handler raising
+    /// `KeyboardInterrupt`, aborts serialization with that exception. Use 0 to never check.
+    pub fn signal_check_interval(mut self, interval: usize) -> Self {
+        self.options.signal_check_interval = interval;
+        self
+    }
+
+    /// Serialize enum variants as instances of Python classes instead of str and dicts.
+    ///
+    /// `classes` maps variant names, either qualified as `"Enum.Variant"` or plain `"Variant"`,
+    /// to the class to call for that variant. Unit variants call the class with no arguments,
+    /// tuple variants with positional arguments and struct variants with keyword arguments.
+    /// Newtype variants pass their value as keyword arguments if it serializes to a dict, or as
+    /// a single positional argument otherwise. Variants without a class serialize as usual.
+    ///
+    /// This is the mirror image of [`Depythonizer::class_tag`](crate::Depythonizer::class_tag).
+    pub fn variant_classes(mut self, classes: &'py Bound<'py, PyDict>) -> Self {
+        self.options.variant_classes = Some(classes);
+        self
+    }
+
+    /// Set how structs and struct variants are represented, as a mapping by default.
+    ///
+    /// The positional layouts are more compact, and `Depythonizer` reads them back by matching
+    /// the items against the struct's fields in order. Fields skipped with
+    /// `#[serde(skip_serializing_if = "...")]` are serialized as `None` to keep the position of
+    /// later fields, and `Depythonizer` reads `None` in a positional slot as a missing field, so
+    /// such fields should also be `#[serde(default)]` unless they are an `Option`.
+    pub fn struct_layout(mut self, layout: StructLayout) -> Self {
+        self.options.struct_layout = layout;
+        self
+    }
+
+    /// Rename struct fields according to `rule`, instead of using their names in Rust.
+    ///
+    /// This applies in addition to any `#[serde(rename)]` attributes, see also
+    /// [`Depythonizer::rename_fields`](crate::Depythonizer::rename_fields).
+    pub fn rename_fields(mut self, rule: RenameRule) -> Self {
+        self.options.rename_fields = Some(rule);
+        self
+    }
+
+    /// Rename enum variants according to `rule`, instead of using their names in Rust.
+    ///
+    /// This applies in addition to any `#[serde(rename)]` attributes, see also
+    /// [`Depythonizer::rename_variants`](crate::Depythonizer::rename_variants).
+    pub fn rename_variants(mut self, rule: RenameRule) -> Self {
+        self.options.rename_variants = Some(rule);
+        self
+    }
+
+    /// Start serializing a value, with fresh state
+    fn start(self) -> PythonizerContext<'py, P> {
+        PythonizerContext {
+            py: self.py,
+            options: self.options,
+            state: Rc::new(PythonizerState {
+                nodes: Cell::new(0),
+                shared: RefCell::default(),
+                _active: shared::SerializingGuard::new(),
+            }),
+            depth: 0,
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<'py, P> PythonizerContext<'py, P> {
+    /// The name of a struct field in Python
+    fn field_name(&self, field: &'static str) -> Cow<'static, str> {
+        match self.options.rename_fields {
+            Some(rule) => Cow::Owned(rule.apply_to_field(field)),
+            None => Cow::Borrowed(field),
+        }
+    }
+
+    /// The name of an enum variant in Python
+    fn variant_name(&self, variant: &'static str) -> Cow<'static, str> {
+        match self.options.rename_variants {
+            Some(rule) => Cow::Owned(rule.apply_to_variant(variant)),
+            None => Cow::Borrowed(variant),
+        }
+    }
+
+    /// The error path segment for the payload of `variant`
+    fn variant_segment(&self, variant: &'static str) -> PathSegment {
+        PathSegment::Key(self.variant_name(variant).into_owned())
+    }
+
+    /// The class to call for an enum variant, if any
+    fn variant_class(&self, name: &str, variant: &str) -> Result<Option<Bound<'py, PyAny>>> {
+        let classes = match self.options.variant_classes {
+            Some(classes) => classes,
+            None => return Ok(None),
+        };
+        if let Some(class) = classes.get_item(format!("{}.{}", name, variant))? {
+            return Ok(Some(class));
+        }
+        Ok(classes.get_item(variant)?)
+    }
+
+    /// Create a serializer for a value nested inside a container
+    fn nested(&self) -> Result<Self> {
+        let nodes = self.state.nodes.get() + 1;
+        self.state.nodes.set(nodes);
+        let interval = self.options.signal_check_interval;
+        if interval != 0 && nodes % interval == 0 {
+            self.py.check_signals()?;
+        }
+        Ok(self.clone())
+    }
+
+    /// Create a serializer for the contents of a container, one level deeper than this one
+    fn enter(self) -> Result<Self> {
+        let depth = self.depth + 1;
+        if depth > self.options.max_depth {
+            return Err(PythonizeError::depth_limit_exceeded(self.options.max_depth));
+        }
+        Ok(Self { depth, ..self })
+    }
+}
+
 #[doc(hidden)]
 pub struct PythonCollectionSerializer<'py, P> {
     items: Vec<Bound<'py, PyAny>>,
-    py: Python<'py>,
-    _types: PhantomData<P>,
+    ser: PythonizerContext<'py, P>,
 }
 
 #[doc(hidden)]
 pub struct PythonTupleVariantSerializer<'py, P> {
     name: &'static str,
     variant: &'static str,
+    class: Option<Bound<'py, PyAny>>,
     inner: PythonCollectionSerializer<'py, P>,
 }
 
@@ -224,25 +449,26 @@ pub struct PythonTupleVariantSerializer<'py, P> {
 pub struct PythonStructVariantSerializer<'py, P: PythonizeTypes<'py>> {
     name: &'static str,
     variant: &'static str,
+    class: Option<Bound<'py, PyAny>>,
     inner: PythonStructDictSerializer<'py, P>,
 }
 
 #[doc(hidden)]
 pub struct PythonStructDictSerializer<'py, P: PythonizeTypes<'py>> {
-    py: Python<'py>,
-    builder: <P::NamedMap as PythonizeNamedMappingType<'py>>::Builder,
-    _types: PhantomData<P>,
+    ser: PythonizerContext<'py, P>,
+    /// `None` if the struct is serialized positionally, into `items`
+    builder: Option<<P::NamedMap as PythonizeNamedMappingType<'py>>::Builder>,
+    items: Vec<Bound<'py, PyAny>>,
 }
 
 #[doc(hidden)]
 pub struct PythonMapSerializer<'py, P: PythonizeTypes<'py>> {
-    py: Python<'py>,
+    ser: PythonizerContext<'py, P>,
     builder: <P::Map as PythonizeMappingType<'py>>::Builder,
     key: Option<Bound<'py, PyAny>>,
-    _types: PhantomData<P>,
 }
 
-impl<'py, P: PythonizeTypes<'py>> Pythonizer<'py, P> {
+impl<'py, P: PythonizeTypes<'py>> PythonizerContext<'py, P> {
     /// The default implementation for serialisation functions.
     #[inline]
     fn serialise_default<T>(self, v: T) -> Result<Bound<'py, PyAny>>
@@ -254,9 +480,46 @@ impl<'py, P: PythonizeTypes<'py>> Pythonizer<'py, P> {
             .map(|x| x.into_any().into_bound())
             .map_err(Into::into)
     }
+
+    fn struct_serializer(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<PythonStructDictSerializer<'py, P>> {
+        let (builder, items) = match self.options.struct_layout {
+            StructLayout::Map => (Some(P::NamedMap::builder(self.py, len, name)?), Vec::new()),
+            StructLayout::Tuple | StructLayout::List => (None, Vec::with_capacity(len)),
+        };
+        Ok(PythonStructDictSerializer {
+            builder,
+            items,
+            ser: self.enter()?,
+        })
+    }
+
+    /// Serialize a [`Shared`](crate::Shared) value, reusing the result for repeated pointers
+    fn serialize_shared<T>(self, value: &T) -> Result<Bound<'py, PyAny>>
+    where
+        T: ?Sized + Serialize,
+    {
+        let (ptr, keep_alive) = match shared::take_ser_slot() {
+            Some(slot) => slot,
+            None => return value.serialize(self),
+        };
+        if let Some((_, obj)) = self.state.shared.borrow().get(&ptr) {
+            return Ok(obj.clone());
+        }
+        let state = self.state.clone();
+        let obj = value.serialize(self)?;
+        state
+            .shared
+            .borrow_mut()
+            .insert(ptr, (keep_alive, obj.clone()));
+        Ok(obj)
+    }
 }
 
-impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
+impl<'py, P: PythonizeTypes<'py>> ser::Serializer for PythonizerContext<'py, P> {
     type Ok = Bound<'py, PyAny>;
     type Error = PythonizeError;
     type SerializeSeq = PythonCollectionSerializer<'py, P>;
@@ -344,21 +607,24 @@ impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
 
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<Bound<'py, PyAny>> {
-        self.serialize_str(variant)
+        if let Some(class) = self.variant_class(name, variant)? {
+            return Ok(class.call0()?);
+        }
+        let variant = self.variant_name(variant);
+        self.serialize_str(&variant)
     }
 
-    fn serialize_newtype_struct<T>(
-        self,
-        _name: &'static str,
-        value: &T,
-    ) -> Result<Bound<'py, PyAny>>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Bound<'py, PyAny>>
     where
         T: ?Sized + Serialize,
     {
+        if name == SHARED_TOKEN {
+            return self.serialize_shared(value);
+        }
         value.serialize(self)
     }
 
@@ -372,12 +638,20 @@ impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
     where
         T: ?Sized + Serialize,
     {
-        let mut m = P::NamedMap::builder(self.py, 1, name)?;
-        P::NamedMap::push_field(
-            &mut m,
-            PyString::new(self.py, variant),
-            value.serialize(self)?,
-        )?;
+        let py = self.py;
+        let class = self.variant_class(name, variant)?;
+        let variant = self.variant_name(variant);
+        let value = value
+            .serialize(self.enter()?)
+            .map_err(|e| e.prepend(PathSegment::Key(variant.to_string())))?;
+        if let Some(class) = class {
+            return Ok(match value.downcast::<PyDict>() {
+                Ok(kwargs) => class.call((), Some(kwargs))?,
+                Err(_) => class.call1((value,))?,
+            });
+        }
+        let mut m = P::NamedMap::builder(py, 1, name)?;
+        P::NamedMap::push_field(&mut m, PyString::new(py, &variant), value)?;
         Ok(P::NamedMap::finish(m)?.into_any())
     }
 
@@ -388,16 +662,14 @@ impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
         };
         Ok(PythonCollectionSerializer {
             items,
-            py: self.py,
-            _types: PhantomData,
+            ser: self.enter()?,
         })
     }
 
     fn serialize_tuple(self, len: usize) -> Result<PythonCollectionSerializer<'py, P>> {
         Ok(PythonCollectionSerializer {
             items: Vec::with_capacity(len),
-            py: self.py,
-            _types: PhantomData,
+            ser: self.enter()?,
         })
     }
 
@@ -416,10 +688,12 @@ impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
         variant: &'static str,
         len: usize,
     ) -> Result<PythonTupleVariantSerializer<'py, P>> {
+        let class = self.variant_class(name, variant)?;
         let inner = self.serialize_tuple(len)?;
         Ok(PythonTupleVariantSerializer {
             name,
             variant,
+            class,
             inner,
         })
     }
@@ -428,8 +702,7 @@ impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
         Ok(PythonMapSerializer {
             builder: P::Map::builder(self.py, len)?,
             key: None,
-            py: self.py,
-            _types: PhantomData,
+            ser: self.enter()?,
         })
     }
 
@@ -438,11 +711,7 @@ impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
         name: &'static str,
         len: usize,
     ) -> Result<PythonStructDictSerializer<'py, P>> {
-        Ok(PythonStructDictSerializer {
-            py: self.py,
-            builder: P::NamedMap::builder(self.py, len, name)?,
-            _types: PhantomData,
-        })
+        self.struct_serializer(name, len)
     }
 
     fn serialize_struct_variant(
@@ -455,15 +724,189 @@ impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
         Ok(PythonStructVariantSerializer {
             name,
             variant,
-            inner: PythonStructDictSerializer {
-                py: self.py,
-                builder: P::NamedMap::builder(self.py, len, variant)?,
-                _types: PhantomData,
-            },
+            class: self.variant_class(name, variant)?,
+            inner: self.struct_serializer(variant, len)?,
         })
     }
 }
 
+/// Each value serialized with a `Pythonizer` gets its own state, so that `Pythonizer` is `Copy`
+impl<'py, P: PythonizeTypes<'py>> ser::Serializer for Pythonizer<'py, P> {
+    type Ok = Bound<'py, PyAny>;
+    type Error = PythonizeError;
+    type SerializeSeq = PythonCollectionSerializer<'py, P>;
+    type SerializeTuple = PythonCollectionSerializer<'py, P>;
+    type SerializeTupleStruct = PythonCollectionSerializer<'py, P>;
+    type SerializeTupleVariant = PythonTupleVariantSerializer<'py, P>;
+    type SerializeMap = PythonMapSerializer<'py, P>;
+    type SerializeStruct = PythonStructDictSerializer<'py, P>;
+    type SerializeStructVariant = PythonStructVariantSerializer<'py, P>;
+
+    fn serialize_bool(self, v: bool) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_u64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_none()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Bound<'py, PyAny>>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.start().serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Bound<'py, PyAny>> {
+        self.start().serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Bound<'py, PyAny>> {
+        self.start()
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Bound<'py, PyAny>>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.start().serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Bound<'py, PyAny>>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.start()
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<PythonCollectionSerializer<'py, P>> {
+        self.start().serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<PythonCollectionSerializer<'py, P>> {
+        self.start().serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<PythonCollectionSerializer<'py, P>> {
+        self.start().serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<PythonTupleVariantSerializer<'py, P>> {
+        self.start()
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<PythonMapSerializer<'py, P>> {
+        self.start().serialize_map(len)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<PythonStructDictSerializer<'py, P>> {
+        self.start().serialize_struct(name, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<PythonStructVariantSerializer<'py, P>> {
+        self.start()
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+}
+
 impl<'py, P: PythonizeTypes<'py>> ser::SerializeSeq for PythonCollectionSerializer<'py, P> {
     type Ok = Bound<'py, PyAny>;
     type Error = PythonizeError;
@@ -472,13 +915,17 @@ impl<'py, P: PythonizeTypes<'py>> ser::SerializeSeq for PythonCollectionSerializ
     where
         T: ?Sized + Serialize,
     {
-        self.items.push(pythonize_custom::<P, _>(self.py, value)?);
+        let index = PathSegment::Index(self.items.len());
+        let item = value
+            .serialize(self.ser.nested()?)
+            .map_err(|e| e.prepend(index))?;
+        self.items.push(item);
         Ok(())
     }
 
     fn end(self) -> Result<Bound<'py, PyAny>> {
-        let instance = P::List::create_sequence(self.py, self.items)?;
-        Ok(instance.into_pyobject(self.py)?.into_any())
+        let instance = P::List::create_sequence(self.ser.py, self.items)?;
+        Ok(instance.into_pyobject(self.ser.py)?.into_any())
     }
 }
 
@@ -494,7 +941,7 @@ impl<'py, P: PythonizeTypes<'py>> ser::SerializeTuple for PythonCollectionSerial
     }
 
     fn end(self) -> Result<Bound<'py, PyAny>> {
-        Ok(PyTuple::new(self.py, self.items)?.into_any())
+        Ok(PyTuple::new(self.ser.py, self.items)?.into_any())
     }
 }
 
@@ -525,15 +972,17 @@ impl<'py, P: PythonizeTypes<'py>> ser::SerializeTupleVariant
         T: ?Sized + Serialize,
     {
         ser::SerializeSeq::serialize_element(&mut self.inner, value)
+            .map_err(|e| e.prepend(self.inner.ser.variant_segment(self.variant)))
     }
 
     fn end(self) -> Result<Bound<'py, PyAny>> {
-        let mut m = P::NamedMap::builder(self.inner.py, 1, self.name)?;
-        P::NamedMap::push_field(
-            &mut m,
-            PyString::new(self.inner.py, self.variant),
-            ser::SerializeTuple::end(self.inner)?,
-        )?;
+        let py = self.inner.ser.py;
+        if let Some(class) = self.class {
+            return Ok(class.call1(PyTuple::new(py, self.inner.items)?)?);
+        }
+        let mut m = P::NamedMap::builder(py, 1, self.name)?;
+        let variant = PyString::new(py, &self.inner.ser.variant_name(self.variant));
+        P::NamedMap::push_field(&mut m, variant, ser::SerializeTuple::end(self.inner)?)?;
         Ok(P::NamedMap::finish(m)?.into_any())
     }
 }
@@ -546,7 +995,7 @@ impl<'py, P: PythonizeTypes<'py>> ser::SerializeMap for PythonMapSerializer<'py,
     where
         T: ?Sized + Serialize,
     {
-        self.key = Some(pythonize_custom::<P, _>(self.py, key)?);
+        self.key = Some(key.serialize(self.ser.nested()?)?);
         Ok(())
     }
 
@@ -554,14 +1003,15 @@ impl<'py, P: PythonizeTypes<'py>> ser::SerializeMap for PythonMapSerializer<'py,
     where
         T: ?Sized + Serialize,
     {
-        P::Map::push_item(
-            &mut self.builder,
-            self.key
-                .take()
-                .expect("serialize_value should always be called after serialize_key"),
-            pythonize_custom::<P, _>(self.py, value)?,
-        )?;
-        Ok(())
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value should always be called after serialize_key");
+        let value = value
+            .serialize(self.ser.nested()?)
+            .map_err(|e| e.prepend(PathSegment::from_key(&key)))?;
+        P::Map::push_item(&mut self.builder, key.clone(), value)
+            .map_err(|e| PythonizeError::from(e).prepend(PathSegment::from_key(&key)))
     }
 
     fn end(self) -> Result<Bound<'py, PyAny>> {
@@ -577,16 +1027,41 @@ impl<'py, P: PythonizeTypes<'py>> ser::SerializeStruct for PythonStructDictSeria
     where
         T: ?Sized + Serialize,
     {
-        P::NamedMap::push_field(
-            &mut self.builder,
-            PyString::new(self.py, key),
-            pythonize_custom::<P, _>(self.py, value)?,
-        )?;
+        let name = self.ser.field_name(key);
+        let value = value.serialize(self.ser.nested()?).map_err(|e| {
+            e.prepend(match self.builder {
+                Some(_) => PathSegment::Key(name.to_string()),
+                None => PathSegment::Index(self.items.len()),
+            })
+        })?;
+        match &mut self.builder {
+            Some(builder) => {
+                let key = PyString::new(self.ser.py, &name);
+                P::NamedMap::push_field(builder, key, value).map_err(|e| {
+                    PythonizeError::from(e).prepend(PathSegment::Key(name.to_string()))
+                })?
+            }
+            None => self.items.push(value),
+        }
+        Ok(())
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<()> {
+        if self.builder.is_none() {
+            self.items.push(self.ser.py.None().into_bound(self.ser.py));
+        }
         Ok(())
     }
 
     fn end(self) -> Result<Bound<'py, PyAny>> {
-        Ok(P::NamedMap::finish(self.builder)?.into_any())
+        let py = self.ser.py;
+        match self.builder {
+            Some(builder) => Ok(P::NamedMap::finish(builder)?.into_any()),
+            None if self.ser.options.struct_layout == StructLayout::List => {
+                Ok(P::List::create_sequence(py, self.items)?.into_any())
+            }
+            None => Ok(PyTuple::new(py, self.items)?.into_any()),
+        }
     }
 }
 
@@ -600,35 +1075,44 @@ impl<'py, P: PythonizeTypes<'py>> ser::SerializeStructVariant
     where
         T: ?Sized + Serialize,
     {
-        P::NamedMap::push_field(
-            &mut self.inner.builder,
-            PyString::new(self.inner.py, key),
-            pythonize_custom::<P, _>(self.inner.py, value)?,
-        )?;
-        Ok(())
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+            .map_err(|e| e.prepend(self.inner.ser.variant_segment(self.variant)))
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<()> {
+        ser::SerializeStruct::skip_field(&mut self.inner, key)
     }
 
     fn end(self) -> Result<Bound<'py, PyAny>> {
-        let v = P::NamedMap::finish(self.inner.builder)?;
-        let mut m = P::NamedMap::builder(self.inner.py, 1, self.name)?;
-        P::NamedMap::push_field(
-            &mut m,
-            PyString::new(self.inner.py, self.variant),
-            v.into_any(),
-        )?;
+        let py = self.inner.ser.py;
+        if let Some(class) = self.class {
+            return Ok(match self.inner.builder {
+                Some(builder) => {
+                    let kwargs = PyDict::new(py);
+                    kwargs.update(&P::NamedMap::finish(builder)?)?;
+                    class.call((), Some(&kwargs))?
+                }
+                None => class.call1(PyTuple::new(py, self.inner.items)?)?,
+            });
+        }
+        let variant = PyString::new(py, &self.inner.ser.variant_name(self.variant));
+        let v = ser::SerializeStruct::end(self.inner)?;
+        let mut m = P::NamedMap::builder(py, 1, self.name)?;
+        P::NamedMap::push_field(&mut m, variant, v.into_any())?;
         Ok(P::NamedMap::finish(m)?.into_any())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::pythonize;
+    use super::{pythonize, Pythonizer, StructLayout};
+    use crate::{depythonize, ClassTag, Depythonizer, RenameRule};
     use maplit::hashmap;
     use pyo3::ffi::c_str;
     use pyo3::prelude::*;
     use pyo3::pybacked::PyBackedStr;
     use pyo3::types::{PyBytes, PyDict};
-    use serde::Serialize;
+    use serde::{Deserialize, Serialize};
 
     fn test_ser<T>(src: T, expected: &str)
     where
@@ -850,8 +1334,161 @@ mod test {
         Python::with_gil(|py| {
             assert!(pythonize(py, serde_bytes::Bytes::new(b"foo"))
                 .expect("bytes will always serialize successfully")
-                .eq(&PyBytes::new(py, b"foo"))
+                .eq(PyBytes::new(py, b"foo"))
                 .expect("bytes will always compare successfully"));
         });
     }
+
+    #[test]
+    fn test_variant_classes() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Side {
+            side: u32,
+        }
+
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        enum Shape {
+            Circle { radius: f64 },
+            Square(Side),
+            Line(i32, i32),
+            Empty,
+        }
+
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "from dataclasses import dataclass\n\
+                     @dataclass\nclass Circle:\n    radius: float\n\
+                     @dataclass\nclass Square:\n    side: int\n\
+                     @dataclass\nclass Line:\n    start: int\n    end: int\n\
+                     classes = {'Shape.Circle': Circle, 'Square': Square, 'Line': Line}"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let classes = locals.get_item("classes").unwrap().unwrap();
+            let classes = classes.downcast_into::<PyDict>().unwrap();
+
+            let shapes = vec![
+                Shape::Circle { radius: 1.5 },
+                Shape::Square(Side { side: 2 }),
+                Shape::Line(3, 4),
+                Shape::Empty,
+            ];
+            let obj = shapes
+                .serialize(Pythonizer::new(py).variant_classes(&classes))
+                .unwrap();
+            assert_eq!(
+                obj.repr().unwrap().to_cow().unwrap(),
+                "[Circle(radius=1.5), Square(side=2), Line(start=3, end=4), 'Empty']"
+            );
+
+            let mut de = Depythonizer::from_object(&obj).class_tag(ClassTag::ClassName);
+            assert_eq!(Vec::<Shape>::deserialize(&mut de).unwrap(), shapes);
+        });
+    }
+
+    #[test]
+    fn test_struct_layout() {
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        struct Point {
+            x: i32,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            label: Option<String>,
+            y: i32,
+            #[serde(skip_serializing_if = "Vec::is_empty", default)]
+            tags: Vec<u8>,
+        }
+
+        #[derive(Debug, Deserialize, Serialize, PartialEq)]
+        enum Shape {
+            Line { start: Point, end: Point },
+        }
+
+        Python::with_gil(|py| {
+            let shape = Shape::Line {
+                start: Point {
+                    x: 1,
+                    label: None,
+                    y: 2,
+                    tags: Vec::new(),
+                },
+                end: Point {
+                    x: 3,
+                    label: Some("end".to_string()),
+                    y: 4,
+                    tags: vec![7],
+                },
+            };
+
+            let obj = shape
+                .serialize(Pythonizer::new(py).struct_layout(StructLayout::Tuple))
+                .unwrap();
+            assert_eq!(
+                obj.repr().unwrap().to_cow().unwrap(),
+                "{'Line': ((1, None, 2, None), (3, 'end', 4, [7]))}"
+            );
+            assert_eq!(depythonize::<Shape>(&obj).unwrap(), shape);
+
+            let obj = shape
+                .serialize(Pythonizer::new(py).struct_layout(StructLayout::List))
+                .unwrap();
+            assert_eq!(
+                obj.repr().unwrap().to_cow().unwrap(),
+                "{'Line': [[1, None, 2, None], [3, 'end', 4, [7]]]}"
+            );
+            assert_eq!(depythonize::<Shape>(&obj).unwrap(), shape);
+        });
+    }
+
+    #[test]
+    fn test_rename() {
+        #[derive(Serialize)]
+        enum Event {
+            UserCreated { user_id: u32 },
+            ServerStopped,
+        }
+
+        #[derive(Serialize)]
+        struct Envelope {
+            event_log: Vec<Event>,
+        }
+
+        Python::with_gil(|py| {
+            let envelope = Envelope {
+                event_log: vec![Event::UserCreated { user_id: 1 }, Event::ServerStopped],
+            };
+            let obj = envelope
+                .serialize(
+                    Pythonizer::new(py)
+                        .rename_fields(RenameRule::CamelCase)
+                        .rename_variants(RenameRule::SnakeCase),
+                )
+                .unwrap();
+            assert_eq!(
+                obj.repr().unwrap().to_cow().unwrap(),
+                "{'eventLog': [{'user_created': {'userId': 1}}, 'server_stopped']}"
+            );
+        });
+    }
+
+    #[test]
+    fn test_max_depth() {
+        Python::with_gil(|py| {
+            let value = vec![vec![vec![1]]];
+            assert!(value.serialize(Pythonizer::new(py).max_depth(3)).is_ok());
+
+            let err = value
+                .serialize(Pythonizer::new(py).max_depth(2))
+                .unwrap_err();
+            assert_eq!(err.to_string(), "maximum nesting depth of 2 exceeded");
+
+            // a `Pythonizer` is `Copy`, so it can be reused with the same options
+            let ser = Pythonizer::new(py).max_depth(2);
+            assert!(vec![vec![1]].serialize(ser).is_ok());
+            assert!(value.serialize(ser).is_err());
+        });
+    }
 }
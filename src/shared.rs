@@ -0,0 +1,280 @@
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Newtype struct name used by [`Shared`] to identify itself to `Pythonizer` and `Depythonizer`
+pub(crate) const SHARED_TOKEN: &str = "$pythonize::private::Shared";
+
+/// Handshake between `Depythonizer` and the `Shared` visitor
+pub(crate) enum DeSlot {
+    /// The Python object was already deserialized to this pointer
+    Hit(Rc<dyn Any>),
+    /// The Python object has not been seen yet, the visitor should store its result
+    Miss,
+    /// The visitor stored a freshly deserialized pointer
+    Stored(Rc<dyn Any>),
+}
+
+thread_local! {
+    static DE_SLOT: RefCell<Option<DeSlot>> = const { RefCell::new(None) };
+    static SER_SLOT: RefCell<Option<(usize, Rc<dyn Any>)>> = const { RefCell::new(None) };
+    /// Number of values being serialized by a `Pythonizer` on this thread
+    static SERIALIZING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Marks a `Pythonizer` as serializing a value on this thread while alive, so that `Shared`
+/// only hands over its pointer when a `Pythonizer` can take it
+pub(crate) struct SerializingGuard(());
+
+impl SerializingGuard {
+    pub(crate) fn new() -> Self {
+        SERIALIZING.with(|s| s.set(s.get() + 1));
+        SerializingGuard(())
+    }
+}
+
+impl Drop for SerializingGuard {
+    fn drop(&mut self) {
+        SERIALIZING.with(|s| s.set(s.get() - 1));
+    }
+}
+
+pub(crate) fn set_de_slot(slot: DeSlot) {
+    DE_SLOT.with(|s| *s.borrow_mut() = Some(slot));
+}
+
+pub(crate) fn take_de_slot() -> Option<DeSlot> {
+    DE_SLOT.with(|s| s.borrow_mut().take())
+}
+
+/// Take the address and a keep-alive handle of the `Shared` value currently being serialized
+pub(crate) fn take_ser_slot() -> Option<(usize, Rc<dyn Any>)> {
+    SER_SLOT.with(|s| s.borrow_mut().take())
+}
+
+/// Reference-counted pointer types which can be wrapped in [`Shared`]
+pub trait SharedPointer: Deref + Clone + 'static {
+    /// Allocate a new pointer holding `value`
+    fn from_value(value: Self::Target) -> Self
+    where
+        Self::Target: Sized;
+
+    /// The address of the pointee, identical for all clones of the pointer
+    fn as_ptr(this: &Self) -> *const ();
+}
+
+impl<T: 'static> SharedPointer for Rc<T> {
+    fn from_value(value: T) -> Self {
+        Rc::new(value)
+    }
+
+    fn as_ptr(this: &Self) -> *const () {
+        Rc::as_ptr(this) as *const ()
+    }
+}
+
+impl<T: 'static> SharedPointer for Arc<T> {
+    fn from_value(value: T) -> Self {
+        Arc::new(value)
+    }
+
+    fn as_ptr(this: &Self) -> *const () {
+        Arc::as_ptr(this) as *const ()
+    }
+}
+
+/// A reference-counted value which keeps its identity across the Python boundary.
+///
+/// `Depythonizer` converts each distinct Python object (by `id()`) into a single pointer,
+/// so repeated occurrences of the same object deserialize to clones of the same `Rc` / `Arc`.
+/// `Pythonizer` converts each distinct pointer into a single Python object, so repeated
+/// clones of the same `Rc` / `Arc` serialize to the same Python object.
+///
+/// Other serializers and deserializers see `Shared<P>` as a transparent newtype around `P::Target`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Shared<P>(pub P);
+
+impl<P> Shared<P> {
+    /// Unwrap the inner pointer
+    pub fn into_inner(self) -> P {
+        self.0
+    }
+}
+
+impl<P: Deref> Deref for Shared<P> {
+    type Target = P::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<P> From<P> for Shared<P> {
+    fn from(pointer: P) -> Self {
+        Shared(pointer)
+    }
+}
+
+impl<P> Serialize for Shared<P>
+where
+    P: SharedPointer,
+    P::Target: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if SERIALIZING.with(Cell::get) == 0 {
+            return serializer.serialize_newtype_struct(SHARED_TOKEN, &*self.0);
+        }
+        let ptr = P::as_ptr(&self.0) as usize;
+        let keep_alive: Rc<dyn Any> = Rc::new(self.0.clone());
+        SER_SLOT.with(|s| *s.borrow_mut() = Some((ptr, keep_alive)));
+        // other serializers used while a `Pythonizer` is active don't take the slot, which
+        // would keep the pointee alive until the next `Shared` is serialized on this thread
+        let _clear = ClearSerSlot;
+        serializer.serialize_newtype_struct(SHARED_TOKEN, &*self.0)
+    }
+}
+
+/// Clears `SER_SLOT` when dropped, including when serialization fails
+struct ClearSerSlot;
+
+impl Drop for ClearSerSlot {
+    fn drop(&mut self) {
+        take_ser_slot();
+    }
+}
+
+impl<'de, P> Deserialize<'de> for Shared<P>
+where
+    P: SharedPointer,
+    P::Target: Deserialize<'de> + Sized,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(SHARED_TOKEN, SharedVisitor(PhantomData))
+    }
+}
+
+struct SharedVisitor<P>(PhantomData<P>);
+
+impl<'de, P> de::Visitor<'de> for SharedVisitor<P>
+where
+    P: SharedPointer,
+    P::Target: Deserialize<'de> + Sized,
+{
+    type Value = Shared<P>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a shared value")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match take_de_slot() {
+            Some(DeSlot::Hit(cached)) => {
+                if let Some(pointer) = cached.downcast_ref::<P>() {
+                    return Ok(Shared(pointer.clone()));
+                }
+                // Same object deserialized as a different type; don't share it
+                P::Target::deserialize(deserializer).map(|v| Shared(P::from_value(v)))
+            }
+            Some(DeSlot::Miss) => {
+                let pointer = P::from_value(P::Target::deserialize(deserializer)?);
+                set_de_slot(DeSlot::Stored(Rc::new(pointer.clone())));
+                Ok(Shared(pointer))
+            }
+            Some(DeSlot::Stored(_)) | None => {
+                P::Target::deserialize(deserializer).map(|v| Shared(P::from_value(v)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use pyo3::ffi::c_str;
+    use pyo3::types::{PyAnyMethods, PyList, PyListMethods};
+    use pyo3::Python;
+    use serde::{Deserialize, Serialize};
+
+    use super::Shared;
+    use crate::{depythonize, pythonize};
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Config {
+        name: String,
+    }
+
+    #[test]
+    fn test_depythonize_keeps_identity() {
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(
+                    c_str!("(lambda c: [c, c, {'name': 'b'}])({'name': 'a'})"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let configs: Vec<Shared<Rc<Config>>> = depythonize(&obj).unwrap();
+
+            assert_eq!(configs[0].name, "a");
+            assert!(Rc::ptr_eq(&configs[0].0, &configs[1].0));
+            assert!(!Rc::ptr_eq(&configs[0].0, &configs[2].0));
+        });
+    }
+
+    #[test]
+    fn test_pythonize_keeps_identity() {
+        Python::with_gil(|py| {
+            let config = Arc::new(Config {
+                name: "a".to_string(),
+            });
+            let configs = vec![
+                Shared(config.clone()),
+                Shared(config),
+                Shared(Arc::new(Config {
+                    name: "b".to_string(),
+                })),
+            ];
+            let obj = pythonize(py, &configs).unwrap();
+            let list = obj.downcast::<PyList>().unwrap();
+
+            assert!(list.get_item(0).unwrap().is(list.get_item(1).unwrap()));
+            assert!(!list.get_item(0).unwrap().is(list.get_item(2).unwrap()));
+        });
+    }
+
+    #[test]
+    fn test_transparent_for_other_formats() {
+        let value = Shared(Rc::new(Config {
+            name: "a".to_string(),
+        }));
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"name":"a"}"#);
+
+        let back: Shared<Rc<Config>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, value);
+
+        // other serializers don't keep the value alive
+        let pointer = Arc::new(Config {
+            name: "b".to_string(),
+        });
+        serde_json::to_string(&Shared(pointer.clone())).unwrap();
+        assert_eq!(Arc::strong_count(&pointer), 1);
+    }
+}
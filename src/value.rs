@@ -0,0 +1,1078 @@
+use pyo3::types::*;
+use pyo3::{Bound, FromPyObject, IntoPyObject, PyErr, PyResult, Python};
+use serde::de::{self, IntoDeserializer};
+use serde::{ser, Deserialize, Serialize};
+
+use crate::de::{visit_signed, visit_unsigned};
+use crate::error::{PythonizeError, Result};
+
+/// The maximum nesting depth of containers when snapshotting a Python object
+const MAX_DEPTH: usize = 128;
+
+/// A Rust-owned copy of a Python value, which can be built and inspected without the GIL.
+///
+/// [`to_value`] produces the same structure as `pythonize` would, without needing a
+/// `Python<'py>` token, so it can run inside `py.allow_threads`. The result is then cheaply
+/// converted to Python objects with `IntoPyObject`.
+///
+/// In the other direction, [`PyValue::from_object`] takes a snapshot of a Python object
+/// which [`from_value`] can later deserialize without the GIL.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PyValue {
+    /// `None`
+    None,
+    /// `bool`
+    Bool(bool),
+    /// `int` which fits in an `i128`
+    Int(i128),
+    /// `int` which does not fit in an `i128`, as its decimal representation
+    BigInt(String),
+    /// `float`
+    Float(f64),
+    /// `str`
+    Str(String),
+    /// `bytes`
+    Bytes(Vec<u8>),
+    /// `list`
+    List(Vec<PyValue>),
+    /// `tuple`
+    Tuple(Vec<PyValue>),
+    /// `dict`, in insertion order
+    Dict(Vec<(PyValue, PyValue)>),
+    /// `set`
+    Set(Vec<PyValue>),
+    /// `frozenset`
+    FrozenSet(Vec<PyValue>),
+}
+
+/// Convert the given data into a [`PyValue`], without needing the GIL
+///
+/// This matches [`pythonize`](crate::pythonize) with the default options: [`Pythonizer`]
+/// settings such as `rename_fields`, `struct_layout` or `variant_classes` are not applied.
+///
+/// [`Pythonizer`]: crate::Pythonizer
+pub fn to_value<T>(value: &T) -> Result<PyValue>
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(ValueSerializer)
+}
+
+/// Convert a [`PyValue`] to an instance of `T`, without needing the GIL
+pub fn from_value<'a, T>(value: &'a PyValue) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    T::deserialize(value)
+}
+
+impl PyValue {
+    /// Take a snapshot of a Python object.
+    ///
+    /// Instances of other sequence and mapping types are copied as `List` and `Dict`.
+    pub fn from_object(obj: &Bound<'_, PyAny>) -> Result<Self> {
+        Self::from_object_at_depth(obj, 0)
+    }
+
+    fn from_object_at_depth(obj: &Bound<'_, PyAny>, depth: usize) -> Result<Self> {
+        let items = |iter: Bound<'_, PyIterator>| -> Result<Vec<PyValue>> {
+            if depth == MAX_DEPTH {
+                return Err(PythonizeError::depth_limit_exceeded(MAX_DEPTH));
+            }
+            iter.map(|item| Self::from_object_at_depth(&item?, depth + 1))
+                .collect()
+        };
+        let pairs = |map: &Bound<'_, PyMapping>| -> Result<Vec<(PyValue, PyValue)>> {
+            if depth == MAX_DEPTH {
+                return Err(PythonizeError::depth_limit_exceeded(MAX_DEPTH));
+            }
+            map.items()?
+                .iter()
+                .map(|item| {
+                    let (key, value): (Bound<'_, PyAny>, Bound<'_, PyAny>) = item.extract()?;
+                    Ok((
+                        Self::from_object_at_depth(&key, depth + 1)?,
+                        Self::from_object_at_depth(&value, depth + 1)?,
+                    ))
+                })
+                .collect()
+        };
+
+        if obj.is_none() {
+            Ok(PyValue::None)
+        } else if let Ok(b) = obj.downcast::<PyBool>() {
+            Ok(PyValue::Bool(b.is_true()))
+        } else if let Ok(int) = obj.downcast::<PyInt>() {
+            match int.extract::<i128>() {
+                Ok(x) => Ok(PyValue::Int(x)),
+                Err(_) => Ok(PyValue::BigInt(int.str()?.to_cow()?.into_owned())),
+            }
+        } else if let Ok(f) = obj.downcast::<PyFloat>() {
+            Ok(PyValue::Float(f.value()))
+        } else if let Ok(s) = obj.downcast::<PyString>() {
+            Ok(PyValue::Str(s.to_cow()?.into_owned()))
+        } else if let Ok(b) = obj.downcast::<PyBytes>() {
+            Ok(PyValue::Bytes(b.as_bytes().to_vec()))
+        } else if let Ok(b) = obj.downcast::<PyByteArray>() {
+            Ok(PyValue::Bytes(b.to_vec()))
+        } else if obj.is_instance_of::<PyTuple>() {
+            Ok(PyValue::Tuple(items(obj.try_iter()?)?))
+        } else if let Ok(dict) = obj.downcast::<PyDict>() {
+            Ok(PyValue::Dict(pairs(dict.as_mapping())?))
+        } else if obj.is_instance_of::<PySet>() {
+            Ok(PyValue::Set(items(obj.try_iter()?)?))
+        } else if obj.is_instance_of::<PyFrozenSet>() {
+            Ok(PyValue::FrozenSet(items(obj.try_iter()?)?))
+        } else if obj.downcast::<PySequence>().is_ok() {
+            Ok(PyValue::List(items(obj.try_iter()?)?))
+        } else if let Ok(map) = obj.downcast::<PyMapping>() {
+            Ok(PyValue::Dict(pairs(map)?))
+        } else {
+            Err(obj.get_type().qualname().map_or_else(
+                |_| PythonizeError::unsupported_type("unknown"),
+                PythonizeError::unsupported_type,
+            ))
+        }
+    }
+
+    /// The name of the Python type this value represents
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            PyValue::None => "NoneType",
+            PyValue::Bool(_) => "bool",
+            PyValue::Int(_) | PyValue::BigInt(_) => "int",
+            PyValue::Float(_) => "float",
+            PyValue::Str(_) => "str",
+            PyValue::Bytes(_) => "bytes",
+            PyValue::List(_) => "list",
+            PyValue::Tuple(_) => "tuple",
+            PyValue::Dict(_) => "dict",
+            PyValue::Set(_) => "set",
+            PyValue::FrozenSet(_) => "frozenset",
+        }
+    }
+
+    /// The truth value of this value, as Python's `bool()` would compute it
+    fn is_truthy(&self) -> bool {
+        match self {
+            PyValue::None => false,
+            PyValue::Bool(b) => *b,
+            PyValue::Int(x) => *x != 0,
+            PyValue::BigInt(_) => true,
+            PyValue::Float(x) => *x != 0.0,
+            PyValue::Str(s) => !s.is_empty(),
+            PyValue::Bytes(b) => !b.is_empty(),
+            PyValue::List(items)
+            | PyValue::Tuple(items)
+            | PyValue::Set(items)
+            | PyValue::FrozenSet(items) => !items.is_empty(),
+            PyValue::Dict(items) => !items.is_empty(),
+        }
+    }
+
+    fn unexpected_type(&self, expected: &str) -> PythonizeError {
+        PythonizeError::unexpected_type(format!(
+            "'{}' object cannot be converted to '{}'",
+            self.type_name(),
+            expected
+        ))
+    }
+}
+
+impl<'py> FromPyObject<'py> for PyValue {
+    fn extract_bound(obj: &Bound<'py, PyAny>) -> PyResult<Self> {
+        Ok(PyValue::from_object(obj)?)
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &PyValue {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(match self {
+            PyValue::None => py.None().into_bound(py),
+            PyValue::Bool(b) => b.into_pyobject(py)?.to_owned().into_any(),
+            PyValue::Int(x) => x.into_pyobject(py)?.into_any(),
+            PyValue::BigInt(s) => py.get_type::<PyInt>().call1((s,))?,
+            PyValue::Float(x) => x.into_pyobject(py)?.into_any(),
+            PyValue::Str(s) => PyString::new(py, s).into_any(),
+            PyValue::Bytes(b) => PyBytes::new(py, b).into_any(),
+            PyValue::List(items) => PyList::new(py, items)?.into_any(),
+            PyValue::Tuple(items) => PyTuple::new(py, items)?.into_any(),
+            PyValue::Dict(items) => {
+                let dict = PyDict::new(py);
+                for (key, value) in items {
+                    dict.set_item(key, value)?;
+                }
+                dict.into_any()
+            }
+            PyValue::Set(items) => PySet::new(py, items)?.into_any(),
+            PyValue::FrozenSet(items) => PyFrozenSet::new(py, items)?.into_any(),
+        })
+    }
+}
+
+impl<'py> IntoPyObject<'py> for PyValue {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = PyErr;
+
+    fn into_pyobject(self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        (&self).into_pyobject(py)
+    }
+}
+
+/// A serializer producing [`PyValue`]s, with the same structure as a default `Pythonizer`
+struct ValueSerializer;
+
+#[doc(hidden)]
+pub struct ValueCollectionSerializer {
+    items: Vec<PyValue>,
+}
+
+#[doc(hidden)]
+pub struct ValueVariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+#[doc(hidden)]
+pub struct ValueMapSerializer {
+    items: Vec<(PyValue, PyValue)>,
+    key: Option<PyValue>,
+}
+
+fn variant(variant: &'static str, value: PyValue) -> PyValue {
+    PyValue::Dict(vec![(PyValue::Str(variant.to_owned()), value)])
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = PyValue;
+    type Error = PythonizeError;
+    type SerializeSeq = ValueCollectionSerializer;
+    type SerializeTuple = ValueCollectionSerializer;
+    type SerializeTupleStruct = ValueCollectionSerializer;
+    type SerializeTupleVariant = ValueVariantSerializer<ValueCollectionSerializer>;
+    type SerializeMap = ValueMapSerializer;
+    type SerializeStruct = ValueMapSerializer;
+    type SerializeStructVariant = ValueVariantSerializer<ValueMapSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<PyValue> {
+        Ok(PyValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<PyValue> {
+        Ok(PyValue::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<PyValue> {
+        Ok(PyValue::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<PyValue> {
+        Ok(PyValue::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<PyValue> {
+        Ok(PyValue::Int(v.into()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<PyValue> {
+        Ok(PyValue::Int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<PyValue> {
+        Ok(PyValue::Int(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<PyValue> {
+        Ok(PyValue::Int(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<PyValue> {
+        Ok(PyValue::Int(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<PyValue> {
+        Ok(PyValue::Int(v.into()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<PyValue> {
+        Ok(match i128::try_from(v) {
+            Ok(v) => PyValue::Int(v),
+            Err(_) => PyValue::BigInt(v.to_string()),
+        })
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<PyValue> {
+        Ok(PyValue::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<PyValue> {
+        Ok(PyValue::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<PyValue> {
+        Ok(PyValue::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<PyValue> {
+        Ok(PyValue::Str(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<PyValue> {
+        Ok(PyValue::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<PyValue> {
+        Ok(PyValue::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<PyValue>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<PyValue> {
+        Ok(PyValue::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<PyValue> {
+        Ok(PyValue::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<PyValue> {
+        Ok(PyValue::Str(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<PyValue>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant_name: &'static str,
+        value: &T,
+    ) -> Result<PyValue>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(variant(variant_name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ValueCollectionSerializer> {
+        Ok(ValueCollectionSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ValueCollectionSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ValueCollectionSerializer> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ValueVariantSerializer<ValueCollectionSerializer>> {
+        Ok(ValueVariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<ValueMapSerializer> {
+        Ok(ValueMapSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<ValueMapSerializer> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ValueVariantSerializer<ValueMapSerializer>> {
+        Ok(ValueVariantSerializer {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+impl ser::SerializeSeq for ValueCollectionSerializer {
+    type Ok = PyValue;
+    type Error = PythonizeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<PyValue> {
+        Ok(PyValue::List(self.items))
+    }
+}
+
+impl ser::SerializeTuple for ValueCollectionSerializer {
+    type Ok = PyValue;
+    type Error = PythonizeError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<PyValue> {
+        Ok(PyValue::Tuple(self.items))
+    }
+}
+
+impl ser::SerializeTupleStruct for ValueCollectionSerializer {
+    type Ok = PyValue;
+    type Error = PythonizeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<PyValue> {
+        ser::SerializeTuple::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for ValueVariantSerializer<ValueCollectionSerializer> {
+    type Ok = PyValue;
+    type Error = PythonizeError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<PyValue> {
+        Ok(variant(self.variant, ser::SerializeTuple::end(self.inner)?))
+    }
+}
+
+impl ser::SerializeMap for ValueMapSerializer {
+    type Ok = PyValue;
+    type Error = PythonizeError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(key.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value should always be called after serialize_key");
+        self.items.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<PyValue> {
+        Ok(PyValue::Dict(self.items))
+    }
+}
+
+impl ser::SerializeStruct for ValueMapSerializer {
+    type Ok = PyValue;
+    type Error = PythonizeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.items.push((
+            PyValue::Str(key.to_owned()),
+            value.serialize(ValueSerializer)?,
+        ));
+        Ok(())
+    }
+
+    fn end(self) -> Result<PyValue> {
+        Ok(PyValue::Dict(self.items))
+    }
+}
+
+impl ser::SerializeStructVariant for ValueVariantSerializer<ValueMapSerializer> {
+    type Ok = PyValue;
+    type Error = PythonizeError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<PyValue> {
+        Ok(variant(
+            self.variant,
+            ser::SerializeStruct::end(self.inner)?,
+        ))
+    }
+}
+
+macro_rules! deserialize_int {
+    ($method:ident => $visit:ident, $ty:ty) => {
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            let x = match self {
+                PyValue::Bool(b) => i128::from(*b),
+                PyValue::Int(x) => *x,
+                PyValue::BigInt(_) => {
+                    return Err(PythonizeError::msg(
+                        "out of range integral type conversion attempted",
+                    ))
+                }
+                _ => return Err(self.unexpected_type("PyInt")),
+            };
+            let x = <$ty>::try_from(x).map_err(|_| {
+                PythonizeError::msg("out of range integral type conversion attempted")
+            })?;
+            visitor.$visit(x)
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &'de PyValue {
+    type Error = PythonizeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::None => visitor.visit_unit(),
+            PyValue::Bool(b) => visitor.visit_bool(*b),
+            PyValue::Int(x) => match u128::try_from(*x) {
+                Ok(x) => visit_unsigned(x, visitor),
+                Err(_) => visit_signed(*x, visitor),
+            },
+            PyValue::BigInt(s) => match s.parse() {
+                Ok(x) => visitor.visit_u128(x),
+                Err(_) => Err(PythonizeError::msg(format!(
+                    "int {} does not fit in {}",
+                    s,
+                    if s.starts_with('-') { "i128" } else { "u128" }
+                ))),
+            },
+            PyValue::Float(x) => visitor.visit_f64(*x),
+            PyValue::Str(s) => visitor.visit_borrowed_str(s),
+            PyValue::Bytes(b) => visitor.visit_borrowed_bytes(b),
+            PyValue::List(_) | PyValue::Tuple(_) | PyValue::Set(_) | PyValue::FrozenSet(_) => {
+                self.deserialize_seq(visitor)
+            }
+            PyValue::Dict(_) => self.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_bool(self.is_truthy())
+    }
+
+    deserialize_int!(deserialize_i8 => visit_i8, i8);
+    deserialize_int!(deserialize_i16 => visit_i16, i16);
+    deserialize_int!(deserialize_i32 => visit_i32, i32);
+    deserialize_int!(deserialize_i64 => visit_i64, i64);
+    deserialize_int!(deserialize_i128 => visit_i128, i128);
+    deserialize_int!(deserialize_u8 => visit_u8, u8);
+    deserialize_int!(deserialize_u16 => visit_u16, u16);
+    deserialize_int!(deserialize_u32 => visit_u32, u32);
+    deserialize_int!(deserialize_u64 => visit_u64, u64);
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let x = match self {
+            PyValue::Bool(b) => u128::from(*b),
+            PyValue::Int(x) => u128::try_from(*x).ok().ok_or_else(|| {
+                PythonizeError::msg("out of range integral type conversion attempted")
+            })?,
+            PyValue::BigInt(s) => s.parse().map_err(|_| {
+                PythonizeError::msg("out of range integral type conversion attempted")
+            })?,
+            _ => return Err(self.unexpected_type("PyInt")),
+        };
+        visitor.visit_u128(x)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::Float(x) => visitor.visit_f64(*x),
+            PyValue::Bool(b) => visitor.visit_f64(f64::from(u8::from(*b))),
+            PyValue::Int(x) => visitor.visit_f64(*x as f64),
+            _ => Err(self.unexpected_type("PyFloat")),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::Str(s) => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => visitor.visit_char(c),
+                    _ => Err(PythonizeError::invalid_length_char()),
+                }
+            }
+            _ => Err(self.unexpected_type("PyString")),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::Str(s) => visitor.visit_borrowed_str(s),
+            _ => Err(self.unexpected_type("PyString")),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::Bytes(b) => visitor.visit_borrowed_bytes(b),
+            _ => Err(self.unexpected_type("PyBytes")),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::None => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::None => visitor.visit_unit(),
+            _ => Err(PythonizeError::msg("expected None")),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::List(items)
+            | PyValue::Tuple(items)
+            | PyValue::Set(items)
+            | PyValue::FrozenSet(items) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(items.iter()))
+            }
+            _ => Err(self.unexpected_type("Sequence")),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::List(items) | PyValue::Tuple(items) if items.len() != len => {
+                Err(PythonizeError::incorrect_sequence_length(len, items.len()))
+            }
+            PyValue::List(items) | PyValue::Tuple(items) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(items.iter()))
+            }
+            _ => Err(self.unexpected_type("Sequence")),
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::Dict(items) => visitor.visit_map(de::value::MapDeserializer::new(
+                items.iter().map(|(k, v)| (k, v)),
+            )),
+            _ => Err(self.unexpected_type("Mapping")),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        // like `Depythonizer`, read structs from their field values in order too
+        match self {
+            PyValue::List(items) | PyValue::Tuple(items) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(items.iter()))
+            }
+            _ => self.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::Str(s) => visitor.visit_enum(s.as_str().into_deserializer()),
+            PyValue::Dict(items) => match items.as_slice() {
+                [(PyValue::Str(variant), value)] => {
+                    visitor.visit_enum(ValueEnumAccess { variant, value })
+                }
+                [_] => Err(PythonizeError::dict_key_not_string()),
+                _ => Err(PythonizeError::invalid_length_enum()),
+            },
+            _ => Err(PythonizeError::invalid_enum_type()),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            PyValue::Str(s) => visitor.visit_borrowed_str(s),
+            _ => Err(PythonizeError::dict_key_not_string()),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+impl<'de> IntoDeserializer<'de, PythonizeError> for &'de PyValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct ValueEnumAccess<'de> {
+    variant: &'de str,
+    value: &'de PyValue,
+}
+
+impl<'de> de::EnumAccess<'de> for ValueEnumAccess<'de> {
+    type Error = PythonizeError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let de: de::value::BorrowedStrDeserializer<'de, PythonizeError> =
+            de::value::BorrowedStrDeserializer::new(self.variant);
+        Ok((seed.deserialize(de)?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for ValueEnumAccess<'de> {
+    type Error = PythonizeError;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.value)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_tuple(self.value, len, visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(self.value, "", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use pyo3::ffi::c_str;
+    use pyo3::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    use super::{from_value, to_value, PyValue};
+    use crate::{depythonize, pythonize};
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    enum Shape {
+        Circle { radius: f64 },
+        Square(u32),
+        Empty,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Drawing {
+        name: String,
+        shapes: Vec<Shape>,
+        origin: (i32, i32),
+        tags: BTreeMap<String, Option<u64>>,
+    }
+
+    fn drawing() -> Drawing {
+        Drawing {
+            name: "sketch".to_string(),
+            shapes: vec![
+                Shape::Circle { radius: 1.5 },
+                Shape::Square(3),
+                Shape::Empty,
+            ],
+            origin: (-1, 2),
+            tags: [
+                ("big".to_string(), Some(u64::MAX)),
+                ("none".to_string(), None),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn test_to_value_matches_pythonize() {
+        Python::with_gil(|py| {
+            let value = drawing();
+            let py_value = py.allow_threads(|| to_value(&value)).unwrap();
+            let from_value = py_value.into_pyobject(py).unwrap();
+            let expected = pythonize(py, &value).unwrap();
+
+            assert!(from_value.eq(&expected).unwrap());
+            assert_eq!(
+                from_value.repr().unwrap().to_cow().unwrap(),
+                expected.repr().unwrap().to_cow().unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn test_from_value_matches_depythonize() {
+        Python::with_gil(|py| {
+            let obj = pythonize(py, &drawing()).unwrap();
+            let snapshot = PyValue::from_object(&obj).unwrap();
+
+            let value: Drawing = py.allow_threads(|| from_value(&snapshot)).unwrap();
+            assert_eq!(value, drawing());
+            assert_eq!(value, depythonize::<Drawing>(&obj).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_snapshot_python_types() {
+        Python::with_gil(|py| {
+            let obj = py
+                .eval(
+                    c_str!("{(1, 'a'): [None, True, 2**200, 1.5, b'x'], 'set': {3}, 'fs': frozenset()}"),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let snapshot: PyValue = obj.extract().unwrap();
+
+            assert_eq!(
+                snapshot,
+                PyValue::Dict(vec![
+                    (
+                        PyValue::Tuple(vec![PyValue::Int(1), PyValue::Str("a".to_string())]),
+                        PyValue::List(vec![
+                            PyValue::None,
+                            PyValue::Bool(true),
+                            PyValue::BigInt(
+                                "1606938044258990275541962092341162602522202993782792835301376"
+                                    .to_string()
+                            ),
+                            PyValue::Float(1.5),
+                            PyValue::Bytes(b"x".to_vec()),
+                        ])
+                    ),
+                    (
+                        PyValue::Str("set".to_string()),
+                        PyValue::Set(vec![PyValue::Int(3)])
+                    ),
+                    (PyValue::Str("fs".to_string()), PyValue::FrozenSet(vec![])),
+                ])
+            );
+
+            let roundtrip = snapshot.into_pyobject(py).unwrap();
+            assert!(roundtrip.eq(&obj).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_big_int_out_of_range() {
+        let big = PyValue::BigInt(
+            "1606938044258990275541962092341162602522202993782792835301376".to_string(),
+        );
+        let err = from_value::<i64>(&big).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "out of range integral type conversion attempted"
+        );
+        let err = from_value::<u8>(&big).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "out of range integral type conversion attempted"
+        );
+    }
+
+    #[test]
+    fn test_big_int_any() {
+        let big = PyValue::BigInt("-170141183460469231731687303715884105729".to_string());
+        let err = from_value::<serde_json::Value>(&big).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "int -170141183460469231731687303715884105729 does not fit in i128"
+        );
+    }
+
+    #[test]
+    fn test_positional_struct() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let value = PyValue::Tuple(vec![PyValue::Int(1), PyValue::Int(2)]);
+        assert_eq!(from_value::<Point>(&value).unwrap(), Point { x: 1, y: 2 });
+    }
+}
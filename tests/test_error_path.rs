@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use pyo3::{
+    ffi::c_str,
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use pythonize::{depythonize, pythonize, PythonizeError};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Root<T> {
+    root_key: String,
+    root_map: BTreeMap<String, Nested<T>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Nested<T> {
+    nested_key: T,
+}
+
+#[derive(Deserialize, Debug)]
+struct CannotSerialize {}
+
+impl Serialize for CannotSerialize {
+    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        Err(serde::ser::Error::custom(
+            "something went intentionally wrong",
+        ))
+    }
+}
+
+fn invalid_root(py: Python<'_>) -> Bound<'_, PyAny> {
+    py.eval(
+        c_str!(
+            "{'root_key': 'root_value', 'root_map': {\
+                'nested_0': {'nested_key': 'nested_value_0'}, \
+                'nested_1': {'nested_key': 1}}}"
+        ),
+        None,
+        None,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_de_path() {
+    Python::with_gil(|py| {
+        let err = depythonize::<Root<String>>(&invalid_root(py)).unwrap_err();
+        assert_eq!(err.path().to_string(), "root_map.nested_1.nested_key");
+        assert_eq!(
+            err.to_string(),
+            "unexpected type: 'int' object cannot be converted to 'PyString' (got 1, expected a string)"
+        );
+
+        let list = PyList::new(py, [0, 1, -2]).unwrap();
+        let err = depythonize::<Vec<u8>>(&list).unwrap_err();
+        assert_eq!(err.path().to_string(), "[2]");
+
+        let err = depythonize::<u8>(&list).unwrap_err();
+        assert!(err.path().segments().is_empty());
+    })
+}
+
+#[test]
+fn test_ser_path() {
+    Python::with_gil(|py| {
+        let root = Root {
+            root_key: String::from("root_value"),
+            root_map: BTreeMap::from([(
+                String::from("nested_0"),
+                Nested {
+                    nested_key: vec![CannotSerialize {}],
+                },
+            )]),
+        };
+
+        let err = pythonize(py, &root).unwrap_err();
+        assert_eq!(err.path().to_string(), "root_map.nested_0.nested_key[0]");
+
+        #[derive(Serialize)]
+        enum Shape {
+            Newtype(CannotSerialize),
+            Tuple(u8, CannotSerialize),
+            Struct { inner: CannotSerialize },
+        }
+
+        let shapes = [
+            Shape::Newtype(CannotSerialize {}),
+            Shape::Tuple(0, CannotSerialize {}),
+            Shape::Struct {
+                inner: CannotSerialize {},
+            },
+        ];
+        let paths: Vec<String> = shapes
+            .iter()
+            .map(|shape| pythonize(py, shape).unwrap_err().path().to_string())
+            .collect();
+        assert_eq!(paths, vec!["Newtype", "Tuple[1]", "Struct.inner"]);
+
+        // a key which Python can't hash
+        let map = BTreeMap::from([(vec![1], 2)]);
+        let err = pythonize(py, &BTreeMap::from([("outer", map)])).unwrap_err();
+        assert_eq!(err.path().to_string(), r#"outer["[1]"]"#);
+    })
+}
+
+#[test]
+fn test_pyerr_path() {
+    Python::with_gil(|py| {
+        let err: PythonizeError = depythonize::<Root<String>>(&invalid_root(py)).unwrap_err();
+        let err = PyErr::from(err);
+        let value = err.value(py);
+
+        let path = value.getattr("path").unwrap();
+        assert_eq!(
+            path.repr().unwrap().to_string(),
+            "('root_map', 'nested_1', 'nested_key')"
+        );
+        let notes: Vec<String> = value.getattr("__notes__").unwrap().extract().unwrap();
+        assert_eq!(notes, vec!["at root_map.nested_1.nested_key"]);
+
+        let locals = PyDict::new(py);
+        locals.set_item("err", value).unwrap();
+        let formatted: String = py
+            .eval(
+                c_str!("''.join(__import__('traceback').format_exception_only(type(err), err))"),
+                None,
+                Some(&locals),
+            )
+            .unwrap()
+            .extract()
+            .unwrap();
+        if py.version_info() >= (3, 11) {
+            assert!(formatted.ends_with("\nat root_map.nested_1.nested_key\n"));
+        }
+    })
+}